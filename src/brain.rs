@@ -1,6 +1,9 @@
 use bevy::prelude::*;
-use rand::RngExt;
+use rand::{Rng, RngExt};
 use rand::seq::IndexedRandom;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::f32::consts::PI;
 
 /// Clamp that maps NaN/Inf to 0.0 instead of propagating.
 fn finite_clamp(v: f32, min: f32, max: f32) -> f32 {
@@ -12,13 +15,20 @@ fn finite_clamp(v: f32, min: f32, max: f32) -> f32 {
 }
 
 const CRABER_MAX_WANT_TO_ATTACK: f32 = 10.;
+/// Most eyes a brain can grow. Eye inputs name their eye by slot, so slots are `0..MAX_EYES`.
+pub const MAX_EYES: u8 = 4;
+/// Range limits of an eye's ray, in world units.
+pub const EYE_MIN_RANGE: f32 = 20.0;
+pub const EYE_MAX_RANGE: f32 = 250.0;
+/// Channels of the pheromone field. Pheromone neurons name their channel, `0..PHEROMONE_CHANNELS`.
+pub const PHEROMONE_CHANNELS: u8 = 2;
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum NeuronType {
     // Input
     AlwaysOn,
     CraberHealth,
-    CraberSpeed, // TODO
+    CraberSpeed, // 0 at rest, approaching 1 as speed grows
     CraberEnergy,
     CraberAge,
 
@@ -34,6 +44,17 @@ pub enum NeuronType {
     // Interval between each update. TODO: Add cost for higher intervals.
     BrainInterval,  // TODO
     LastReproduced, // Decay-based: 1.0 after reproduction, decays toward 0
+    CraberAngularVelocity, // Spin, between -1 (turning left) and +1 (turning right)
+    VelocityDirection,     // Direction of motion relative to facing, -1 (left) to +1 (right), 0 at rest
+    RecentlyCollided,      // Decay-based: 1.0 after bumping into another craber
+    WasAttacked,           // Decay-based: 1.0 after being bitten
+    PheromoneConcentration(u8), // Channel's concentration under the craber, 0 to approaching 1
+    PheromoneGradient(u8),      // Uphill direction of the channel, -1 (left) to +1 (right)
+    // Eye in the given slot: 1 at contact, falling to 0 at the eye's range or when it sees nothing
+    EyeDistance(u8),
+    EyeFood(u8),   // 1 if the eye's ray first hits food
+    EyeCraber(u8), // 1 if the eye's ray first hits a craber
+    EyeWall(u8),   // 1 if the eye's ray first hits a wall
     // Hidden
     Hidden,
     // Output
@@ -47,51 +68,100 @@ pub enum NeuronType {
     WantSexualReproduction,
     WantToAttack,
     WantToDefend,
+    EmitPheromone(u8), // How much of the channel to deposit (clamped to 0-1)
+    WantGeneTransfer,  // Swaps genes with a touching craber when both are above the threshold
 }
 
 impl NeuronType {
-    pub fn random_input_type() -> Self {
-        let input_types = vec![
-            NeuronType::AlwaysOn,
-            NeuronType::CraberHealth,
-            NeuronType::CraberSpeed,
-            NeuronType::CraberEnergy,
-            NeuronType::CraberAge,
-            NeuronType::NearestFoodAngle,
-            NeuronType::NearestFoodDistance,
-            NeuronType::NearestCraberAngle,
-            NeuronType::NearestCraberDistance,
-            NeuronType::NearestWallAngle,
-            NeuronType::NearestWallDistance,
-            NeuronType::NearestCraberGeneticCloseness,
-            NeuronType::BrainInterval,
-            NeuronType::LastReproduced,
-        ];
-        let mut rng = rand::rng();
-        *input_types.choose(&mut rng).unwrap()
+    pub const INPUT_TYPES: [NeuronType; 22] = [
+        NeuronType::AlwaysOn,
+        NeuronType::CraberHealth,
+        NeuronType::CraberSpeed,
+        NeuronType::CraberEnergy,
+        NeuronType::CraberAge,
+        NeuronType::NearestFoodAngle,
+        NeuronType::NearestFoodDistance,
+        NeuronType::NearestCraberAngle,
+        NeuronType::NearestCraberDistance,
+        NeuronType::NearestWallAngle,
+        NeuronType::NearestWallDistance,
+        NeuronType::NearestCraberGeneticCloseness,
+        NeuronType::BrainInterval,
+        NeuronType::LastReproduced,
+        NeuronType::CraberAngularVelocity,
+        NeuronType::VelocityDirection,
+        NeuronType::RecentlyCollided,
+        NeuronType::WasAttacked,
+        NeuronType::PheromoneConcentration(0),
+        NeuronType::PheromoneConcentration(1),
+        NeuronType::PheromoneGradient(0),
+        NeuronType::PheromoneGradient(1),
+    ];
+
+    pub fn random_input_type<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        *Self::INPUT_TYPES.choose(rng).unwrap()
+    }
+
+    /// Inputs fed by the eye in `slot`. These are inputs on top of `INPUT_TYPES`, valid only in
+    /// brains that have that eye.
+    pub fn eye_input_types(slot: u8) -> [NeuronType; 4] {
+        [
+            NeuronType::EyeDistance(slot),
+            NeuronType::EyeFood(slot),
+            NeuronType::EyeCraber(slot),
+            NeuronType::EyeWall(slot),
+        ]
+    }
+
+    /// Slot of the eye feeding this input, if it is an eye input.
+    pub fn eye_slot(&self) -> Option<u8> {
+        match *self {
+            NeuronType::EyeDistance(slot)
+            | NeuronType::EyeFood(slot)
+            | NeuronType::EyeCraber(slot)
+            | NeuronType::EyeWall(slot) => Some(slot),
+            _ => None,
+        }
     }
 
     pub fn random_hidden_type() -> Self {
         NeuronType::Hidden
     }
 
-    pub fn random_output_type() -> Self {
-        let output_types = vec![
-            NeuronType::KickStrength,
-            NeuronType::KickRate,
-            NeuronType::AlignVelocity,
-            NeuronType::Rotate,
-            NeuronType::RotateRate,
-            NeuronType::ModifyBrainInterval,
-            NeuronType::WantToReproduce,
-            NeuronType::WantSexualReproduction,
-        ];
-        let mut rng = rand::rng();
-        *output_types.choose(&mut rng).unwrap()
+    pub const OUTPUT_TYPES: [NeuronType; 13] = [
+        NeuronType::KickStrength,
+        NeuronType::KickRate,
+        NeuronType::AlignVelocity,
+        NeuronType::Rotate,
+        NeuronType::RotateRate,
+        NeuronType::ModifyBrainInterval,
+        NeuronType::WantToReproduce,
+        NeuronType::WantSexualReproduction,
+        NeuronType::WantToAttack,
+        NeuronType::WantToDefend,
+        NeuronType::EmitPheromone(0),
+        NeuronType::EmitPheromone(1),
+        NeuronType::WantGeneTransfer,
+    ];
+
+    pub fn random_output_type<R: Rng + ?Sized>(rng: &mut R) -> Self {
+        *Self::OUTPUT_TYPES.choose(rng).unwrap()
     }
 }
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+/// How a brain tick propagates values through the network.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub enum BrainEvaluation {
+    /// Every neuron reads the values of the previous step, so each connection on a path adds
+    /// one step of latency. A tick runs `brain_relaxation_steps` steps.
+    #[default]
+    Relaxation,
+    /// Hidden neurons are updated in topological order, so acyclic paths reach the outputs
+    /// within one tick. Connections closing a cycle read the previous tick's value.
+    Topological,
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum ActivationFunction {
     None,
     Sigmoid,
@@ -114,8 +184,7 @@ impl ActivationFunction {
             ActivationFunction::Sin => value.sin(),
         }
     }
-    pub fn random() -> Self {
-        let mut rng = rand::rng();
+    pub fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
         match rng.random_range(0..6) {
             0 => ActivationFunction::None,
             1 => ActivationFunction::Sigmoid,
@@ -129,33 +198,126 @@ impl ActivationFunction {
     }
 }
 
-#[derive(PartialEq, Clone, Copy, Debug)]
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Neuron {
     pub neuron_type: NeuronType,
     // Optional activation function. If none is provided, the value is used directly.
     pub activation_function: ActivationFunction,
     pub value: f32,
+    // Historical marking of hidden neurons. Inputs and outputs are identified by type and keep 0.
+    pub innovation: u64,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Connection {
     pub from_id: usize, // Neuron id. < 100 is input < 200 is hidden < 300 is output.
     pub to_id: usize,   // Neuron id. < 100 is input < 200 is hidden < 300 is output.
     pub weight: f32,    // -1.0 to 1.0
     pub bias: f32,      // -1.0 to 1.0
     pub enabled: bool,
+    pub innovation: u64, // Same for every connection between the same pair of neurons
+    /// 0.0 to 1.0. Weight of this allele when expressed next to the other chromosome's. A gene
+    /// carried by one chromosome only is expressed when dominant (at least 0.5).
+    pub dominance: f32,
+}
+
+/// Ray-cast eye. Each brain tick it reports the distance and kind of the first body its ray hits.
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Eye {
+    /// Eye inputs refer to their eye by slot, so it stays the same while other eyes come and go.
+    pub slot: u8,
+    /// Ray direction relative to facing, in radians. Positive is to the right.
+    pub angle: f32,
+    /// Ray length, between `EYE_MIN_RANGE` and `EYE_MAX_RANGE`.
+    pub range: f32,
+}
+
+/// What an eye's ray hit first.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum EyeHitKind {
+    Food,
+    Craber,
+    Wall,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct EyeHit {
+    pub kind: EyeHitKind,
+    pub distance: f32,
+}
+
+/// Identity of a neuron across brains, independent of its index in any one of them.
+/// Inputs and outputs are identified by type, hidden neurons by innovation number.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum NeuronKey {
+    Typed(NeuronType),
+    Hidden(u64),
+}
+
+/// Global source of NEAT-style historical markings.
+/// Every new hidden neuron gets a fresh innovation number, while a new connection gets the
+/// number already assigned to its pair of neurons if that connection ever appeared before.
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+pub struct InnovationRegistry {
+    next_neuron: u64,
+    next_connection: u64,
+    connections: HashMap<(NeuronKey, NeuronKey), u64>,
+}
+
+impl Default for InnovationRegistry {
+    /// Starts with the genes of `Brain::default()`, which every spawned craber shares.
+    fn default() -> Self {
+        let brain = Brain::default();
+        let mut registry = Self {
+            next_neuron: brain.hidden_layers.iter().map(|n| n.innovation + 1).max().unwrap_or(0),
+            next_connection: brain.connections.iter().map(|c| c.innovation + 1).max().unwrap_or(0),
+            connections: HashMap::new(),
+        };
+        for conn in &brain.connections {
+            let key = (brain.neuron_key(conn.from_id).unwrap(), brain.neuron_key(conn.to_id).unwrap());
+            registry.connections.insert(key, conn.innovation);
+        }
+        registry
+    }
+}
+
+impl InnovationRegistry {
+    pub fn new_neuron(&mut self) -> u64 {
+        self.next_neuron += 1;
+        self.next_neuron - 1
+    }
+
+    pub fn connection(&mut self, from: NeuronKey, to: NeuronKey) -> u64 {
+        let next = &mut self.next_connection;
+        *self.connections.entry((from, to)).or_insert_with(|| {
+            *next += 1;
+            *next - 1
+        })
+    }
+}
+
+/// Connection genes of two brains lined up by innovation number.
+pub struct GeneAlignment {
+    pub matching: usize,
+    /// Unmatched genes within the other brain's innovation range.
+    pub disjoint: usize,
+    /// Unmatched genes newer than anything in the other brain.
+    pub excess: usize,
+    /// Mean absolute weight difference of matching genes.
+    pub mean_weight_difference: f32,
 }
 /// Craber brain
 /// Neurons are mapped to indexes using this map:
 ///     inputs          [0..99]
 ///     hidden_layers   [100..199]
 ///     outputs         [200..inf]
-#[derive(Component, Debug, Clone)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Brain {
     pub inputs: Vec<Neuron>,
     pub outputs: Vec<Neuron>,
     pub hidden_layers: Vec<Neuron>,
     pub connections: Vec<Connection>,
+    pub eyes: Vec<Eye>,
 }
 
 impl Brain {
@@ -166,66 +328,79 @@ impl Brain {
                 neuron_type: NeuronType::AlwaysOn,
                 activation_function: ActivationFunction::None,
                 value: 1.0,
+                innovation: 0,
             },
             Neuron {
                 neuron_type: NeuronType::CraberHealth,
                 activation_function: ActivationFunction::None,
                 value: 0.0,
+                innovation: 0,
             },
             Neuron {
                 neuron_type: NeuronType::CraberEnergy,
                 activation_function: ActivationFunction::None,
                 value: 0.0,
+                innovation: 0,
             },
             Neuron {
                 neuron_type: NeuronType::CraberAge,
                 activation_function: ActivationFunction::None,
                 value: 0.0,
+                innovation: 0,
             },
             Neuron {
                 neuron_type: NeuronType::NearestFoodAngle,
                 activation_function: ActivationFunction::None,
                 value: 0.0,
+                innovation: 0,
             },
             Neuron {
                 neuron_type: NeuronType::NearestFoodDistance,
                 activation_function: ActivationFunction::None,
                 value: 0.0,
+                innovation: 0,
             },
             Neuron {
                 neuron_type: NeuronType::NearestCraberAngle,
                 activation_function: ActivationFunction::None,
                 value: 0.0,
+                innovation: 0,
             },
             Neuron {
                 neuron_type: NeuronType::NearestCraberDistance,
                 activation_function: ActivationFunction::None,
                 value: 0.0,
+                innovation: 0,
             },
             Neuron {
                 neuron_type: NeuronType::NearestWallAngle,
                 activation_function: ActivationFunction::None,
                 value: 0.0,
+                innovation: 0,
             },
             Neuron {
                 neuron_type: NeuronType::NearestWallDistance,
                 activation_function: ActivationFunction::None,
                 value: 0.0,
+                innovation: 0,
             },
             Neuron {
                 neuron_type: NeuronType::NearestCraberGeneticCloseness,
                 activation_function: ActivationFunction::None,
                 value: 0.0,
+                innovation: 0,
             },
             Neuron {
                 neuron_type: NeuronType::BrainInterval,
                 activation_function: ActivationFunction::None,
                 value: 0.0,
+                innovation: 0,
             },
             Neuron {
                 neuron_type: NeuronType::LastReproduced,
                 activation_function: ActivationFunction::None,
                 value: 0.0,
+                innovation: 0,
             },
         ];
         let outputs = vec![
@@ -233,52 +408,62 @@ impl Brain {
                 neuron_type: NeuronType::KickStrength,
                 activation_function: ActivationFunction::ReLU,
                 value: 0.0,
+                innovation: 0,
             },
             Neuron {
                 neuron_type: NeuronType::KickRate,
                 activation_function: ActivationFunction::ReLU,
                 value: 0.0,
+                innovation: 0,
             },
             Neuron {
                 neuron_type: NeuronType::AlignVelocity,
                 activation_function: ActivationFunction::Sigmoid,
                 value: 0.0,
+                innovation: 0,
             },
             Neuron {
                 neuron_type: NeuronType::Rotate,
                 activation_function: ActivationFunction::None,
                 value: 0.0,
+                innovation: 0,
             },
             Neuron {
                 neuron_type: NeuronType::WantToAttack,
                 activation_function: ActivationFunction::None,
                 value: 0.0,
+                innovation: 0,
             },
             Neuron {
                 neuron_type: NeuronType::ModifyBrainInterval,
                 activation_function: ActivationFunction::Sigmoid,
                 value: 0.0,
+                innovation: 0,
             },
             Neuron {
                 neuron_type: NeuronType::RotateRate,
                 activation_function: ActivationFunction::ReLU,
                 value: 0.0,
+                innovation: 0,
             },
             Neuron {
                 neuron_type: NeuronType::WantToReproduce,
                 activation_function: ActivationFunction::None,
                 value: 0.0,
+                innovation: 0,
             },
             Neuron {
                 neuron_type: NeuronType::WantSexualReproduction,
                 activation_function: ActivationFunction::None,
                 value: 0.0,
+                innovation: 0,
             },
         ];
         let hidden_layers = vec![Neuron {
             neuron_type: NeuronType::Hidden,
             activation_function: ActivationFunction::Sin,
             value: 0.0,
+            innovation: 0,
         }];
         let connections = vec![
             // AlwaysOn -> KickStrength (ReLU(0.2)=0.2, gentle kicks)
@@ -288,6 +473,8 @@ impl Brain {
                 weight: 0.5,
                 bias: 0.0,
                 enabled: true,
+                innovation: 0,
+                dominance: 0.5,
             },
             // AlwaysOn -> KickRate (ReLU(0.2)=0.2, infrequent)
             Connection {
@@ -296,6 +483,8 @@ impl Brain {
                 weight: 0.05,
                 bias: 0.0,
                 enabled: true,
+                innovation: 1,
+                dominance: 0.5,
             },
             // AlwaysOn -> AlignVelocity (sigmoid(2.0)=0.88, mostly ship-like)
             Connection {
@@ -304,6 +493,8 @@ impl Brain {
                 weight: 2.0,
                 bias: 0.0,
                 enabled: true,
+                innovation: 2,
+                dominance: 0.5,
             },
             // FoodAngle -> Hidden
             Connection {
@@ -312,6 +503,8 @@ impl Brain {
                 weight: 1.0,
                 bias: 0.0,
                 enabled: true,
+                innovation: 3,
+                dominance: 0.5,
             },
             // CraberAngle -> Hidden
             Connection {
@@ -320,6 +513,8 @@ impl Brain {
                 weight: 0.1,
                 bias: 0.0,
                 enabled: false,
+                innovation: 4,
+                dominance: 0.5,
             },
             // Hidden -> Rotate
             Connection {
@@ -328,6 +523,8 @@ impl Brain {
                 weight: 2.5,
                 bias: 0.0,
                 enabled: true,
+                innovation: 5,
+                dominance: 0.5,
             },
             // AlwaysOn -> WantToAttack
             Connection {
@@ -336,6 +533,8 @@ impl Brain {
                 weight: 0.2,
                 bias: 0.0,
                 enabled: true,
+                innovation: 6,
+                dominance: 0.5,
             },
             // AlwaysOn -> RotateRate
             Connection {
@@ -344,6 +543,8 @@ impl Brain {
                 weight: 0.5,
                 bias: 0.0,
                 enabled: true,
+                innovation: 7,
+                dominance: 0.5,
             },
             // AlwaysOn -> WantToReproduce (always on: 1.0 * 1.5 = 1.5 >= 1.0)
             Connection {
@@ -352,6 +553,8 @@ impl Brain {
                 weight: 1.5,
                 bias: 0.0,
                 enabled: true,
+                innovation: 8,
+                dominance: 0.5,
             },
             // AlwaysOn -> WantSexualReproduction (1.0 * 0.9 = 0.9, in fallback range)
             Connection {
@@ -360,13 +563,22 @@ impl Brain {
                 weight: 0.98,
                 bias: 0.0,
                 enabled: true,
+                innovation: 9,
+                dominance: 0.5,
             },
         ];
+        // One eye looking straight ahead. It feeds nothing until mutation adds its inputs.
+        let eyes = vec![Eye {
+            slot: 0,
+            angle: 0.0,
+            range: 100.0,
+        }];
         Self {
             inputs,
             outputs,
             hidden_layers,
             connections,
+            eyes,
         }
     }
     pub fn get_neuron(&self, id: usize) -> Option<&Neuron> {
@@ -381,6 +593,99 @@ impl Brain {
         }
     }
 
+    /// Ids of all neurons: inputs, then hidden, then outputs.
+    pub fn neuron_ids(&self) -> impl DoubleEndedIterator<Item = usize> {
+        (0..self.inputs.len())
+            .chain((0..self.hidden_layers.len()).map(|i| i + 100))
+            .chain((0..self.outputs.len()).map(|i| i + 200))
+    }
+
+    pub fn neuron_key(&self, id: usize) -> Option<NeuronKey> {
+        let neuron = self.get_neuron(id)?;
+        if (100..200).contains(&id) {
+            Some(NeuronKey::Hidden(neuron.innovation))
+        } else {
+            Some(NeuronKey::Typed(neuron.neuron_type))
+        }
+    }
+
+    /// Gives hidden neurons fresh innovation numbers and re-registers every connection,
+    /// for brains whose markings come from another run (e.g. imported genomes).
+    pub fn restamp_innovations(&mut self, innovations: &mut InnovationRegistry) {
+        for neuron in self.hidden_layers.iter_mut() {
+            neuron.innovation = innovations.new_neuron();
+        }
+        for i in 0..self.connections.len() {
+            let (from_id, to_id) = (self.connections[i].from_id, self.connections[i].to_id);
+            if let (Some(from), Some(to)) = (self.neuron_key(from_id), self.neuron_key(to_id)) {
+                self.connections[i].innovation = innovations.connection(from, to);
+            }
+        }
+    }
+
+    /// Checks the invariants mutation and evaluation rely on: every layer is non-empty and fits
+    /// its id range, input and output types are unique and in the right layer, eye inputs have
+    /// their eye, and every connection points at an existing neuron.
+    pub fn validate(&self) -> Result<(), String> {
+        // Mutation picks random neurons from every layer, so none of them may be empty
+        if self.inputs.is_empty() || self.hidden_layers.is_empty() || self.outputs.is_empty() {
+            return Err("brain needs at least one input, hidden and output neuron".to_string());
+        }
+        if self.inputs.len() > 100 || self.hidden_layers.len() > 100 {
+            return Err(format!(
+                "{} inputs and {} hidden neurons overflow their id ranges",
+                self.inputs.len(),
+                self.hidden_layers.len()
+            ));
+        }
+        let mut slots = HashSet::new();
+        for eye in &self.eyes {
+            if eye.slot >= MAX_EYES || !slots.insert(eye.slot) {
+                return Err(format!("eye slot {} is out of range or taken twice", eye.slot));
+            }
+            if !eye.angle.is_finite() || !(EYE_MIN_RANGE..=EYE_MAX_RANGE).contains(&eye.range) {
+                return Err(format!("eye {} has angle {} and range {}", eye.slot, eye.angle, eye.range));
+            }
+        }
+        let mut seen = HashSet::new();
+        for neuron in &self.inputs {
+            if let Some(slot) = neuron.neuron_type.eye_slot() {
+                if !self.has_eye(slot) {
+                    return Err(format!("{:?} has no eye", neuron.neuron_type));
+                }
+            } else if !NeuronType::INPUT_TYPES.contains(&neuron.neuron_type) {
+                return Err(format!("{:?} is not an input type", neuron.neuron_type));
+            }
+            if !seen.insert(neuron.neuron_type) {
+                return Err(format!("duplicate input {:?}", neuron.neuron_type));
+            }
+        }
+        for neuron in &self.hidden_layers {
+            if neuron.neuron_type != NeuronType::Hidden {
+                return Err(format!("{:?} is not a hidden type", neuron.neuron_type));
+            }
+        }
+        for neuron in &self.outputs {
+            if !NeuronType::OUTPUT_TYPES.contains(&neuron.neuron_type) {
+                return Err(format!("{:?} is not an output type", neuron.neuron_type));
+            }
+            if !seen.insert(neuron.neuron_type) {
+                return Err(format!("duplicate output {:?}", neuron.neuron_type));
+            }
+        }
+        for conn in &self.connections {
+            for id in [conn.from_id, conn.to_id] {
+                if self.get_neuron(id).is_none() {
+                    return Err(format!(
+                        "connection {} -> {} references missing neuron {}",
+                        conn.from_id, conn.to_id, id
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
     pub fn set_neuron(&mut self, id: usize, neuron: Neuron) {
         if id < 100 {
             self.inputs[id] = neuron;
@@ -391,6 +696,55 @@ impl Brain {
         }
     }
 
+    /// Removes a neuron together with its connections. Ids after it in the same layer shift
+    /// down by one, so the remaining connections are renumbered to match.
+    pub fn remove_neuron(&mut self, id: usize) {
+        let layer_end = if id < 100 {
+            if id >= self.inputs.len() {
+                return;
+            }
+            self.inputs.remove(id);
+            100
+        } else if id < 200 {
+            if id - 100 >= self.hidden_layers.len() {
+                return;
+            }
+            self.hidden_layers.remove(id - 100);
+            200
+        } else {
+            if id - 200 >= self.outputs.len() {
+                return;
+            }
+            self.outputs.remove(id - 200);
+            usize::MAX
+        };
+        self.connections
+            .retain(|conn| conn.from_id != id && conn.to_id != id);
+        let reindex = |neuron_id: &mut usize| {
+            if *neuron_id > id && *neuron_id < layer_end {
+                *neuron_id -= 1;
+            }
+        };
+        for conn in self.connections.iter_mut() {
+            reindex(&mut conn.from_id);
+            reindex(&mut conn.to_id);
+        }
+    }
+
+    pub fn has_eye(&self, slot: u8) -> bool {
+        self.eyes.iter().any(|eye| eye.slot == slot)
+    }
+
+    /// Removes the eye in `slot` together with its input neurons.
+    pub fn remove_eye(&mut self, slot: u8) {
+        self.eyes.retain(|eye| eye.slot != slot);
+        for id in (0..self.inputs.len()).rev() {
+            if self.inputs[id].neuron_type.eye_slot() == Some(slot) {
+                self.remove_neuron(id);
+            }
+        }
+    }
+
     pub fn set_neuron_value(&mut self, id: usize, new_value: f32) {
         if id < 100 {
             self.inputs[id].value = new_value;
@@ -401,6 +755,7 @@ impl Brain {
         }
     }
 
+    /// Sets the input neuron of the given type, if the brain has one.
     pub fn update_input(&mut self, input_neuron_type: NeuronType, value: f32) {
         for neuron in self.inputs.iter_mut() {
             if neuron.neuron_type == input_neuron_type {
@@ -417,108 +772,86 @@ impl Brain {
         input_types
     }
 
+    /// Value of the output neuron of the given type, wherever it sits in `outputs`.
+    /// Brains without that output read 0.0.
+    pub fn output_value(&self, output_type: NeuronType) -> f32 {
+        self.outputs
+            .iter()
+            .find(|neuron| neuron.neuron_type == output_type)
+            .map_or(0.0, |neuron| neuron.value)
+    }
+
+    /// Input types this brain doesn't have yet, including those of its eyes.
+    pub fn missing_input_types(&self) -> Vec<NeuronType> {
+        NeuronType::INPUT_TYPES
+            .into_iter()
+            .chain(self.eyes.iter().flat_map(|eye| NeuronType::eye_input_types(eye.slot)))
+            .filter(|t| !self.inputs.iter().any(|n| n.neuron_type == *t))
+            .collect()
+    }
+
+    /// Output types this brain doesn't have yet.
+    pub fn missing_output_types(&self) -> Vec<NeuronType> {
+        NeuronType::OUTPUT_TYPES
+            .into_iter()
+            .filter(|t| !self.outputs.iter().any(|n| n.neuron_type == *t))
+            .collect()
+    }
+
     pub fn get_rotation(&self) -> f32 {
-        let mut rotation = 0.0;
-        for neuron in self.outputs.iter() {
-            if neuron.neuron_type == NeuronType::Rotate {
-                rotation = neuron.value;
-            }
-        }
-        rotation
+        self.output_value(NeuronType::Rotate)
     }
     pub fn get_kick_strength(&self) -> f32 {
-        for neuron in self.outputs.iter() {
-            if neuron.neuron_type == NeuronType::KickStrength {
-                return neuron.value;
-            }
-        }
-        0.0
+        self.output_value(NeuronType::KickStrength)
     }
     pub fn get_rotate_rate(&self) -> f32 {
-        for neuron in self.outputs.iter() {
-            if neuron.neuron_type == NeuronType::RotateRate {
-                return neuron.value;
-            }
-        }
-        0.0
+        self.output_value(NeuronType::RotateRate)
     }
     pub fn get_kick_rate(&self) -> f32 {
-        for neuron in self.outputs.iter() {
-            if neuron.neuron_type == NeuronType::KickRate {
-                return neuron.value;
-            }
-        }
-        0.0
+        self.output_value(NeuronType::KickRate)
     }
     pub fn get_align_velocity(&self) -> f32 {
-        for neuron in self.outputs.iter() {
-            if neuron.neuron_type == NeuronType::AlignVelocity {
-                return neuron.value;
-            }
-        }
-        0.0
+        self.output_value(NeuronType::AlignVelocity)
     }
     pub fn get_want_to_attack(&self) -> f32 {
-        let mut want_to_attack = 0.0;
-        for neuron in self.outputs.iter() {
-            if neuron.neuron_type == NeuronType::WantToAttack {
-                want_to_attack = neuron.value;
-                if want_to_attack > CRABER_MAX_WANT_TO_ATTACK {
-                    want_to_attack = CRABER_MAX_WANT_TO_ATTACK;
-                }
-            }
-        }
-        want_to_attack
+        self.output_value(NeuronType::WantToAttack)
+            .min(CRABER_MAX_WANT_TO_ATTACK)
     }
 
     pub fn get_want_to_defent(&self) -> f32 {
-        let mut want_to_defend = 0.0;
-        for neuron in self.outputs.iter() {
-            if neuron.neuron_type == NeuronType::WantToDefend {
-                want_to_defend = neuron.value;
-            }
-        }
-        want_to_defend
+        self.output_value(NeuronType::WantToDefend)
     }
 
     pub fn get_modify_brain_interval(&self) -> f32 {
-        for neuron in self.outputs.iter() {
-            if neuron.neuron_type == NeuronType::ModifyBrainInterval {
-                return neuron.value;
-            }
-        }
-        0.0
+        self.output_value(NeuronType::ModifyBrainInterval)
     }
 
     pub fn get_want_to_reproduce(&self) -> f32 {
-        for neuron in self.outputs.iter() {
-            if neuron.neuron_type == NeuronType::WantToReproduce {
-                return neuron.value;
-            }
-        }
-        0.0
+        self.output_value(NeuronType::WantToReproduce)
     }
 
     pub fn get_want_sexual_reproduction(&self) -> f32 {
-        for neuron in self.outputs.iter() {
-            if neuron.neuron_type == NeuronType::WantSexualReproduction {
-                return neuron.value;
-            }
-        }
-        0.0
+        self.output_value(NeuronType::WantSexualReproduction)
     }
 
-    pub fn crossover_brain(
+    pub fn get_want_gene_transfer(&self) -> f32 {
+        self.output_value(NeuronType::WantGeneTransfer)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn crossover_brain<R: Rng + ?Sized>(
         &self,
         other: &Brain,
         mutation_chance: f32,
         mutation_amount: f32,
         insertion_chance: f32,
+        deletion_chance: f32,
+        innovations: &mut InnovationRegistry,
+        rng: &mut R,
     ) -> Brain {
-        let mut rng = rand::rng();
 
         // Inputs: union of both parents' input types
-        let mut input_types_seen = std::collections::HashSet::new();
+        let mut input_types_seen = HashSet::new();
         let mut inputs = Vec::new();
         for neuron in self.inputs.iter().chain(other.inputs.iter()) {
             if input_types_seen.insert(neuron.neuron_type) {
@@ -526,35 +859,36 @@ impl Brain {
             }
         }
 
-        // Hidden: align by index, 50/50 pick activation
-        let max_hidden = self.hidden_layers.len().max(other.hidden_layers.len());
+        // Hidden: align by innovation. Matching genes pick either parent's activation 50/50,
+        // disjoint and excess genes are inherited with a 50% chance
         let mut hidden_layers = Vec::new();
-        for i in 0..max_hidden {
-            let a = self.hidden_layers.get(i);
-            let b = other.hidden_layers.get(i);
-            match (a, b) {
-                (Some(na), Some(nb)) => {
-                    let activation = if rng.random_range(0.0..1.0) < 0.5 {
-                        na.activation_function
-                    } else {
-                        nb.activation_function
-                    };
-                    hidden_layers.push(Neuron {
-                        neuron_type: NeuronType::Hidden,
-                        activation_function: activation,
-                        value: 0.0,
-                    });
-                }
-                (Some(n), None) | (None, Some(n)) => {
-                    if rng.random_range(0.0..1.0) < 0.5 {
-                        hidden_layers.push(Neuron {
-                            neuron_type: NeuronType::Hidden,
-                            activation_function: n.activation_function,
-                            value: 0.0,
-                        });
-                    }
-                }
-                (None, None) => {}
+        for neuron in &self.hidden_layers {
+            let other_neuron = other
+                .hidden_layers
+                .iter()
+                .find(|n| n.innovation == neuron.innovation);
+            let activation = match other_neuron {
+                Some(on) if rng.random_range(0.0..1.0) >= 0.5 => on.activation_function,
+                Some(_) => neuron.activation_function,
+                None if rng.random_range(0.0..1.0) < 0.5 => neuron.activation_function,
+                None => continue,
+            };
+            hidden_layers.push(Neuron {
+                activation_function: activation,
+                value: 0.0,
+                ..*neuron
+            });
+        }
+        for neuron in &other.hidden_layers {
+            if self.hidden_layers.iter().any(|n| n.innovation == neuron.innovation) {
+                continue;
+            }
+            // Hidden ids only reach up to 199
+            if rng.random_range(0.0..1.0) < 0.5 && hidden_layers.len() < 100 {
+                hidden_layers.push(Neuron {
+                    value: 0.0,
+                    ..*neuron
+                });
             }
         }
         if hidden_layers.is_empty() {
@@ -562,14 +896,17 @@ impl Brain {
                 neuron_type: NeuronType::Hidden,
                 activation_function: ActivationFunction::Sin,
                 value: 0.0,
+                innovation: innovations.new_neuron(),
             });
         }
 
         // Outputs: union of both parents' output types; shared types → 50/50 pick activation
-        let mut output_types_seen = std::collections::HashSet::new();
+        let mut output_types_seen = HashSet::new();
         let mut outputs = Vec::new();
         for neuron in self.outputs.iter() {
-            output_types_seen.insert(neuron.neuron_type);
+            if !output_types_seen.insert(neuron.neuron_type) {
+                continue;
+            }
             let other_neuron = other
                 .outputs
                 .iter()
@@ -584,186 +921,355 @@ impl Brain {
                 neuron.activation_function
             };
             outputs.push(Neuron {
-                neuron_type: neuron.neuron_type,
                 activation_function: activation,
                 value: 0.0,
+                ..*neuron
             });
         }
         for neuron in other.outputs.iter() {
             if !output_types_seen.contains(&neuron.neuron_type) {
                 output_types_seen.insert(neuron.neuron_type);
                 outputs.push(Neuron {
-                    neuron_type: neuron.neuron_type,
-                    activation_function: neuron.activation_function,
                     value: 0.0,
+                    ..*neuron
                 });
             }
         }
 
-        // Build neuron type→index maps for remapping connections
-        let input_type_to_idx: std::collections::HashMap<NeuronType, usize> = inputs
-            .iter()
-            .enumerate()
-            .map(|(i, n)| (n.neuron_type, i))
-            .collect();
-        let output_type_to_idx: std::collections::HashMap<NeuronType, usize> = outputs
-            .iter()
-            .enumerate()
-            .map(|(i, n)| (n.neuron_type, i + 200))
-            .collect();
-
-        // Helper to remap a neuron ID from a parent brain to the child brain
-        let remap_id = |id: usize, parent: &Brain| -> Option<usize> {
-            if id < 100 {
-                // Input neuron — remap by type
-                let neuron_type = parent.inputs.get(id)?.neuron_type;
-                input_type_to_idx.get(&neuron_type).copied()
-            } else if id < 200 {
-                // Hidden neuron — keep index if within bounds
-                let idx = id - 100;
-                if idx < hidden_layers.len() {
-                    Some(id)
-                } else {
-                    None
-                }
-            } else {
-                // Output neuron — remap by type
-                let idx = id - 200;
-                let neuron_type = parent.outputs.get(idx)?.neuron_type;
-                output_type_to_idx.get(&neuron_type).copied()
+        // Eyes: union by slot, so every inherited eye input keeps its eye. Shared slots pick
+        // either parent's eye 50/50
+        let mut eyes = Vec::new();
+        for eye in &self.eyes {
+            match other.eyes.iter().find(|e| e.slot == eye.slot) {
+                Some(other_eye) if rng.random_range(0.0..1.0) >= 0.5 => eyes.push(*other_eye),
+                _ => eyes.push(*eye),
+            }
+        }
+        for eye in &other.eyes {
+            if !self.has_eye(eye.slot) {
+                eyes.push(*eye);
             }
+        }
+
+        let mut child = Brain {
+            inputs,
+            outputs,
+            hidden_layers,
+            connections: Vec::new(),
+            eyes,
         };
 
-        // Connections: match by (from_type, to_type); shared → 50/50 pick weight+bias
-        type ConnKey = (usize, usize); // (from_neuron_type_hash, to_neuron_type_hash)
+        // Map neuron keys to child ids for remapping connections
+        let child_ids: HashMap<NeuronKey, usize> = child
+            .neuron_ids()
+            .rev() // first neuron of a duplicated type wins
+            .filter_map(|id| Some((child.neuron_key(id)?, id)))
+            .collect();
+        let remap = |conn: &Connection, parent: &Brain| -> Option<Connection> {
+            Some(Connection {
+                from_id: *child_ids.get(&parent.neuron_key(conn.from_id)?)?,
+                to_id: *child_ids.get(&parent.neuron_key(conn.to_id)?)?,
+                ..conn.clone()
+            })
+        };
 
-        // Collect parent A connections (remapped)
-        let mut a_conns: Vec<(ConnKey, Connection)> = Vec::new();
+        // Connections: align by innovation. Matching genes pick either parent 50/50,
+        // disjoint and excess genes of this parent are kept, the mate's with a 50% chance.
+        // Genes whose neurons didn't make it into the child are dropped.
+        let other_conns: HashMap<u64, &Connection> = other
+            .connections
+            .iter()
+            .map(|c| (c.innovation, c))
+            .collect();
+        let mut connections = Vec::new();
         for conn in &self.connections {
-            if let (Some(new_from), Some(new_to)) =
-                (remap_id(conn.from_id, self), remap_id(conn.to_id, self))
-            {
-                let key = (new_from, new_to);
-                a_conns.push((
-                    key,
-                    Connection {
-                        from_id: new_from,
-                        to_id: new_to,
-                        weight: conn.weight,
-                        bias: conn.bias,
-                        enabled: conn.enabled,
-                    },
-                ));
-            }
+            let gene = match other_conns.get(&conn.innovation) {
+                Some(other_conn) if rng.random_range(0.0..1.0) >= 0.5 => remap(other_conn, other),
+                _ => remap(conn, self),
+            };
+            connections.extend(gene);
         }
-
-        // Collect parent B connections (remapped)
-        let mut b_conn_map: std::collections::HashMap<ConnKey, Connection> =
-            std::collections::HashMap::new();
+        let self_innovations: HashSet<u64> = self.connections.iter().map(|c| c.innovation).collect();
         for conn in &other.connections {
-            if let (Some(new_from), Some(new_to)) =
-                (remap_id(conn.from_id, other), remap_id(conn.to_id, other))
-            {
-                let key = (new_from, new_to);
-                b_conn_map.insert(
-                    key,
-                    Connection {
-                        from_id: new_from,
-                        to_id: new_to,
-                        weight: conn.weight,
-                        bias: conn.bias,
-                        enabled: conn.enabled,
-                    },
-                );
+            if !self_innovations.contains(&conn.innovation) && rng.random_range(0.0..1.0) < 0.5 {
+                connections.extend(remap(conn, other));
             }
         }
+        child.connections = connections;
 
-        let mut connections = Vec::new();
-        let mut seen_keys = std::collections::HashSet::new();
-        for (key, a_conn) in &a_conns {
-            seen_keys.insert(*key);
-            if let Some(b_conn) = b_conn_map.get(key) {
-                // Shared: 50/50 pick
-                let conn = if rng.random_range(0.0..1.0) < 0.5 {
-                    a_conn
-                } else {
-                    b_conn
-                };
-                connections.push(conn.clone());
-            } else {
-                connections.push(a_conn.clone());
-            }
-        }
-        for (key, b_conn) in &b_conn_map {
-            if !seen_keys.contains(key) {
-                if rng.random_range(0.0..1.0) < 0.5 {
-                    connections.push(b_conn.clone());
-                }
+        // Apply standard mutation on the crossover result
+        child.new_mutated_brain(
+            mutation_chance,
+            mutation_amount,
+            insertion_chance,
+            deletion_chance,
+            innovations,
+            rng,
+        )
+    }
+
+    /// Phenotype of a craber carrying `self` and `other` as its two chromosomes. Neurons and eyes
+    /// of both are kept, activations and eyes carried by both come from `self`. Connection genes
+    /// carried by both blend by dominance, genes carried by one are expressed when dominant.
+    pub fn express(&self, other: &Brain) -> Brain {
+        let mut input_types_seen = HashSet::new();
+        let inputs = self
+            .inputs
+            .iter()
+            .chain(other.inputs.iter())
+            .filter(|n| input_types_seen.insert(n.neuron_type))
+            .copied()
+            .collect();
+        let mut hidden_layers = self.hidden_layers.clone();
+        for neuron in &other.hidden_layers {
+            // Hidden ids only reach up to 199
+            if hidden_layers.len() < 100
+                && !self.hidden_layers.iter().any(|n| n.innovation == neuron.innovation)
+            {
+                hidden_layers.push(*neuron);
             }
         }
+        let mut output_types_seen = HashSet::new();
+        let outputs = self
+            .outputs
+            .iter()
+            .chain(other.outputs.iter())
+            .filter(|n| output_types_seen.insert(n.neuron_type))
+            .copied()
+            .collect();
+        let mut eyes = self.eyes.clone();
+        eyes.extend(other.eyes.iter().filter(|e| !self.has_eye(e.slot)));
 
-        let child = Brain {
+        let mut phenotype = Brain {
             inputs,
             outputs,
             hidden_layers,
-            connections,
+            connections: Vec::new(),
+            eyes,
+        };
+        let phenotype_ids: HashMap<NeuronKey, usize> = phenotype
+            .neuron_ids()
+            .rev()
+            .filter_map(|id| Some((phenotype.neuron_key(id)?, id)))
+            .collect();
+        let remap = |conn: &Connection, chromosome: &Brain| -> Option<Connection> {
+            Some(Connection {
+                from_id: *phenotype_ids.get(&chromosome.neuron_key(conn.from_id)?)?,
+                to_id: *phenotype_ids.get(&chromosome.neuron_key(conn.to_id)?)?,
+                ..conn.clone()
+            })
         };
 
-        // Apply standard mutation on the crossover result
-        child.new_mutated_brain(mutation_chance, mutation_amount, insertion_chance, 0.0)
-    }
-
-    pub fn genetic_closeness(&self, other: &Brain) -> f32 {
-        // Structural similarity: Jaccard index of connection keys
-        let self_keys: std::collections::HashSet<(usize, usize)> = self
+        let other_conns: HashMap<u64, &Connection> = other
             .connections
             .iter()
-            .map(|c| (c.from_id, c.to_id))
+            .map(|c| (c.innovation, c))
             .collect();
-        let other_keys: std::collections::HashSet<(usize, usize)> = other
-            .connections
-            .iter()
-            .map(|c| (c.from_id, c.to_id))
+        let mut connections = Vec::new();
+        for conn in &self.connections {
+            let gene = match other_conns.get(&conn.innovation) {
+                Some(other_conn) => {
+                    let total = conn.dominance + other_conn.dominance;
+                    let share = if total > 0.0 { conn.dominance / total } else { 0.5 };
+                    let dominant = if share >= 0.5 { conn } else { *other_conn };
+                    remap(conn, self).map(|gene| Connection {
+                        weight: conn.weight * share + other_conn.weight * (1.0 - share),
+                        bias: conn.bias * share + other_conn.bias * (1.0 - share),
+                        enabled: dominant.enabled,
+                        dominance: dominant.dominance,
+                        ..gene
+                    })
+                }
+                None if conn.dominance >= 0.5 => remap(conn, self),
+                None => None,
+            };
+            connections.extend(gene);
+        }
+        for conn in &other.connections {
+            if conn.dominance >= 0.5
+                && !self.connections.iter().any(|c| c.innovation == conn.innovation)
+            {
+                connections.extend(remap(conn, other));
+            }
+        }
+        phenotype.connections = connections;
+        phenotype
+    }
+
+    /// Takes over the current value of every neuron `other` has too, so a brain expressed again
+    /// mid-life keeps its live state.
+    pub fn copy_neuron_values(&mut self, other: &Brain) {
+        let values: HashMap<NeuronKey, f32> = other
+            .neuron_ids()
+            .filter_map(|id| Some((other.neuron_key(id)?, other.get_neuron(id)?.value)))
             .collect();
-        let intersection = self_keys.intersection(&other_keys).count() as f32;
-        let union = self_keys.union(&other_keys).count() as f32;
-        let structural = if union > 0.0 {
-            intersection / union
-        } else {
-            1.0
-        };
+        for neuron in self.inputs.iter_mut().chain(self.outputs.iter_mut()) {
+            if let Some(&value) = values.get(&NeuronKey::Typed(neuron.neuron_type)) {
+                neuron.value = value;
+            }
+        }
+        for neuron in self.hidden_layers.iter_mut() {
+            if let Some(&value) = values.get(&NeuronKey::Hidden(neuron.innovation)) {
+                neuron.value = value;
+            }
+        }
+    }
 
-        // Weight similarity: average 1 - |diff| for shared connections
-        let other_map: std::collections::HashMap<(usize, usize), &Connection> = other
+    /// Horizontal gene transfer: copies a random connected subgraph of up to `max_connections` of
+    /// `donor`'s connection genes this brain doesn't carry, with the neurons and eyes they need.
+    /// Returns how many connections were copied.
+    pub fn receive_genes<R: Rng + ?Sized>(&mut self, donor: &Brain, max_connections: usize, rng: &mut R) -> usize {
+        let own: HashSet<u64> = self.connections.iter().map(|c| c.innovation).collect();
+        let candidates: Vec<&Connection> = donor
             .connections
             .iter()
-            .map(|c| ((c.from_id, c.to_id), c))
+            .filter(|c| !own.contains(&c.innovation))
             .collect();
-        let mut weight_sim_sum = 0.0;
-        let mut weight_count = 0;
+        let Some(&seed) = candidates.choose(rng) else {
+            return 0;
+        };
+        // Grow from the seed through genes sharing a neuron with the subgraph
+        let mut subgraph = vec![seed];
+        let mut neurons = HashSet::from([seed.from_id, seed.to_id]);
+        while subgraph.len() < max_connections {
+            let frontier: Vec<&Connection> = candidates
+                .iter()
+                .filter(|c| {
+                    (neurons.contains(&c.from_id) || neurons.contains(&c.to_id))
+                        && !subgraph.iter().any(|s| s.innovation == c.innovation)
+                })
+                .copied()
+                .collect();
+            let Some(&next) = frontier.choose(rng) else {
+                break;
+            };
+            neurons.extend([next.from_id, next.to_id]);
+            subgraph.push(next);
+        }
+
+        let mut copied = 0;
+        for conn in subgraph {
+            let Some(from_id) = self.adopt_neuron(donor, conn.from_id) else {
+                continue;
+            };
+            let Some(to_id) = self.adopt_neuron(donor, conn.to_id) else {
+                continue;
+            };
+            self.connections.push(Connection {
+                from_id,
+                to_id,
+                ..conn.clone()
+            });
+            copied += 1;
+        }
+        copied
+    }
+
+    /// Id in this brain of `donor`'s neuron `id`, adding the neuron and its eye when missing.
+    /// `None` when its layer is full.
+    fn adopt_neuron(&mut self, donor: &Brain, id: usize) -> Option<usize> {
+        let key = donor.neuron_key(id)?;
+        if let Some(own) = self.neuron_ids().find(|&own| self.neuron_key(own) == Some(key)) {
+            return Some(own);
+        }
+        let neuron = *donor.get_neuron(id)?;
+        if id < 100 {
+            if self.inputs.len() >= 100 {
+                return None;
+            }
+            if let Some(slot) = neuron.neuron_type.eye_slot()
+                && !self.has_eye(slot)
+            {
+                self.eyes.push(*donor.eyes.iter().find(|eye| eye.slot == slot)?);
+            }
+            self.inputs.push(neuron);
+            Some(self.inputs.len() - 1)
+        } else if id < 200 {
+            if self.hidden_layers.len() >= 100 {
+                return None;
+            }
+            self.hidden_layers.push(neuron);
+            Some(self.hidden_layers.len() - 1 + 100)
+        } else {
+            self.outputs.push(neuron);
+            Some(self.outputs.len() - 1 + 200)
+        }
+    }
+
+    pub fn align_connections(&self, other: &Brain) -> GeneAlignment {
+        let max_innovation = |brain: &Brain| brain.connections.iter().map(|c| c.innovation).max();
+        let (self_max, other_max) = (max_innovation(self), max_innovation(other));
+        let other_map: HashMap<u64, &Connection> =
+            other.connections.iter().map(|c| (c.innovation, c)).collect();
+        let self_innovations: HashSet<u64> = self.connections.iter().map(|c| c.innovation).collect();
+
+        let mut alignment = GeneAlignment {
+            matching: 0,
+            disjoint: 0,
+            excess: 0,
+            mean_weight_difference: 0.0,
+        };
+        let mut weight_difference_sum = 0.0;
         for conn in &self.connections {
-            if let Some(oc) = other_map.get(&(conn.from_id, conn.to_id)) {
-                weight_sim_sum += 1.0 - (conn.weight - oc.weight).abs().min(2.0) / 2.0;
-                weight_count += 1;
+            if let Some(oc) = other_map.get(&conn.innovation) {
+                alignment.matching += 1;
+                weight_difference_sum += (conn.weight - oc.weight).abs();
+            } else if other_max.is_none_or(|max| conn.innovation > max) {
+                alignment.excess += 1;
+            } else {
+                alignment.disjoint += 1;
+            }
+        }
+        for conn in &other.connections {
+            if self_innovations.contains(&conn.innovation) {
+                continue;
+            }
+            if self_max.is_none_or(|max| conn.innovation > max) {
+                alignment.excess += 1;
+            } else {
+                alignment.disjoint += 1;
             }
         }
-        let weight_sim = if weight_count > 0 {
-            weight_sim_sum / weight_count as f32
+        if alignment.matching > 0 {
+            alignment.mean_weight_difference = weight_difference_sum / alignment.matching as f32;
+        }
+        alignment
+    }
+
+    pub fn genetic_closeness(&self, other: &Brain) -> f32 {
+        let alignment = self.align_connections(other);
+
+        // Structural similarity: share of matching connection genes
+        let genes = alignment.matching + alignment.disjoint + alignment.excess;
+        let structural = if genes > 0 {
+            alignment.matching as f32 / genes as f32
+        } else {
+            1.0
+        };
+
+        // Weight similarity of matching connection genes
+        let weight_sim = if alignment.matching > 0 {
+            1.0 - alignment.mean_weight_difference.min(2.0) / 2.0
         } else {
             0.0
         };
 
-        // Activation similarity: matching activations in hidden layers
-        let max_hidden = self.hidden_layers.len().min(other.hidden_layers.len());
+        // Activation similarity: matching activations of hidden neurons with the same innovation
         let mut act_match = 0;
-        for i in 0..max_hidden {
-            if self.hidden_layers[i].activation_function
-                == other.hidden_layers[i].activation_function
+        let mut shared_hidden = 0;
+        for neuron in &self.hidden_layers {
+            if let Some(on) = other
+                .hidden_layers
+                .iter()
+                .find(|n| n.innovation == neuron.innovation)
             {
-                act_match += 1;
+                shared_hidden += 1;
+                if neuron.activation_function == on.activation_function {
+                    act_match += 1;
+                }
             }
         }
-        let total_hidden = self.hidden_layers.len().max(other.hidden_layers.len());
+        let total_hidden = self.hidden_layers.len() + other.hidden_layers.len() - shared_hidden;
         let activation_sim = if total_hidden > 0 {
             act_match as f32 / total_hidden as f32
         } else {
@@ -773,7 +1279,86 @@ impl Brain {
         0.5 * structural + 0.3 * weight_sim + 0.2 * activation_sim
     }
 
-    pub fn feed_forward(&mut self) {
+    /// Runs one brain tick.
+    pub fn feed_forward(&mut self, evaluation: BrainEvaluation, relaxation_steps: u32) {
+        match evaluation {
+            BrainEvaluation::Relaxation => {
+                for _ in 0..relaxation_steps.max(1) {
+                    self.relaxation_step();
+                }
+            }
+            BrainEvaluation::Topological => self.topological_step(),
+        }
+    }
+
+    /// Order in which `BrainEvaluation::Topological` updates hidden neurons, as indexes into
+    /// `hidden_layers`. Sources come before the neurons they feed; cycles are broken at their
+    /// lowest-indexed neuron, whose inputs from inside the cycle then read last tick's values.
+    pub fn hidden_evaluation_order(&self) -> Vec<usize> {
+        let count = self.hidden_layers.len();
+        let hidden = 100..100 + count;
+        let mut in_degree = vec![0usize; count];
+        let mut targets = vec![Vec::new(); count];
+        for conn in self.connections.iter().filter(|c| c.enabled) {
+            if hidden.contains(&conn.from_id) && hidden.contains(&conn.to_id) && conn.from_id != conn.to_id {
+                targets[conn.from_id - 100].push(conn.to_id - 100);
+                in_degree[conn.to_id - 100] += 1;
+            }
+        }
+
+        let mut order = Vec::with_capacity(count);
+        let mut done = vec![false; count];
+        while order.len() < count {
+            let next = (0..count)
+                .find(|&i| !done[i] && in_degree[i] == 0)
+                .or_else(|| (0..count).find(|&i| !done[i]))
+                .unwrap();
+            done[next] = true;
+            order.push(next);
+            for &target in &targets[next] {
+                in_degree[target] = in_degree[target].saturating_sub(1);
+            }
+        }
+        order
+    }
+
+    /// Weighted input of a neuron, read from the current neuron values.
+    fn incoming_sum(&self, id: usize) -> f32 {
+        let mut sum = 0.0f32;
+        for conn in &self.connections {
+            if !conn.enabled || conn.to_id != id {
+                continue;
+            }
+            if let Some(from) = self.get_neuron(conn.from_id) {
+                sum += from.value * conn.weight + conn.bias;
+            }
+        }
+        sum
+    }
+
+    /// Updates hidden neurons in place in topological order, then the outputs, so a tick
+    /// carries inputs all the way to the outputs.
+    fn topological_step(&mut self) {
+        for h_idx in self.hidden_evaluation_order() {
+            let sum = self.incoming_sum(100 + h_idx);
+            self.hidden_layers[h_idx].value = finite_clamp(
+                self.hidden_layers[h_idx].activation_function.calculate(sum),
+                -1e6,
+                1e6,
+            );
+        }
+        for o_idx in 0..self.outputs.len() {
+            let sum = self.incoming_sum(200 + o_idx);
+            self.outputs[o_idx].value = finite_clamp(
+                self.outputs[o_idx].activation_function.calculate(sum),
+                -1e6,
+                1e6,
+            );
+        }
+    }
+
+    /// Moves every value one connection further: all neurons read the previous step's values.
+    fn relaxation_step(&mut self) {
         // Snapshot all neuron values into prev (double-buffer)
         let max_id = 200 + self.outputs.len();
         let mut prev = vec![0.0f32; max_id];
@@ -909,45 +1494,54 @@ impl Brain {
         result
     }
 
-    pub fn new_mutated_brain(
+    pub fn new_mutated_brain<R: Rng + ?Sized>(
         &self,
         mutation_chance: f32,
         mutation_amount: f32,
         insertion_chance: f32,
-        _deletion_chance: f32,
+        deletion_chance: f32,
+        innovations: &mut InnovationRegistry,
+        rng: &mut R,
     ) -> Self {
         let mut mutated_brain = self.clone();
-        let mut rng = rand::rng();
 
         // Insertion mutations
-        if rand::random_range(0.0..1.) < insertion_chance {
-            // rng between input/hidden/output
-            // TODO. placeholder for only hidden layers
-            match rng.random_range(0..2) {
-                // TODO Implement outputs insertion
-                0 => {
+        if rng.random_range(0.0..1.) < insertion_chance {
+            // rng between hidden/input/output
+            match rng.random_range(0..3) {
+                // Hidden ids only reach up to 199
+                0 if mutated_brain.hidden_layers.len() < 100 => {
                     let new_neuron = Neuron {
                         neuron_type: NeuronType::random_hidden_type(),
-                        activation_function: ActivationFunction::random(),
+                        activation_function: ActivationFunction::random(rng),
                         value: 0.0,
+                        innovation: innovations.new_neuron(),
                     };
                     mutated_brain.hidden_layers.push(new_neuron);
                 }
                 1 => {
-                    let new_neuron = Neuron {
-                        neuron_type: NeuronType::random_input_type(),
-                        activation_function: ActivationFunction::random(),
-                        value: 0.0,
-                    };
-                    mutated_brain.inputs.push(new_neuron);
+                    // Sensors write to inputs by type, so a second neuron of a type adds nothing
+                    if let Some(&input_type) = mutated_brain.missing_input_types().choose(rng) {
+                        let new_neuron = Neuron {
+                            neuron_type: input_type,
+                            activation_function: ActivationFunction::random(rng),
+                            value: if input_type == NeuronType::AlwaysOn { 1.0 } else { 0.0 },
+                            innovation: 0,
+                        };
+                        mutated_brain.inputs.push(new_neuron);
+                    }
                 }
                 2 => {
-                    let new_neuron = Neuron {
-                        neuron_type: NeuronType::random_output_type(),
-                        activation_function: ActivationFunction::random(),
-                        value: 0.0,
-                    };
-                    mutated_brain.outputs.push(new_neuron);
+                    // Each output type drives one behaviour, so only add types the brain lacks
+                    if let Some(&output_type) = mutated_brain.missing_output_types().choose(rng) {
+                        let new_neuron = Neuron {
+                            neuron_type: output_type,
+                            activation_function: ActivationFunction::random(rng),
+                            value: 0.0,
+                            innovation: 0,
+                        };
+                        mutated_brain.outputs.push(new_neuron);
+                    }
                 }
                 _ => {}
             }
@@ -963,6 +1557,8 @@ impl Brain {
                 weight: 0.0,
                 bias: 0.0,
                 enabled: false,
+                innovation: 0,
+                dominance: 0.5,
             };
             match rng.random_range(0..3) {
                 0 => {
@@ -976,6 +1572,8 @@ impl Brain {
                                 weight: rng.random_range(-1.0..1.0),
                                 bias: rng.random_range(-1.0..1.0),
                                 enabled: true,
+                                innovation: 0,
+                                dominance: rng.random_range(0.0..1.0),
                             };
                         }
                         1 | _ => {
@@ -985,18 +1583,22 @@ impl Brain {
                                 weight: rng.random_range(-1.0..1.0),
                                 bias: rng.random_range(-1.0..1.0),
                                 enabled: true,
+                                innovation: 0,
+                                dominance: rng.random_range(0.0..1.0),
                             };
                         }
                     }
                 }
                 1 => {
-                    let to_b = rng.random_range(0..self.outputs.len()) + 200;
+                    let to_b = rng.random_range(0..mutated_brain.outputs.len()) + 200;
                     new_connection = Connection {
                         from_id: from_b,
                         to_id: to_b,
                         weight: rng.random_range(-1.0..1.0),
                         bias: rng.random_range(-1.0..1.0),
                         enabled: true,
+                        innovation: 0,
+                        dominance: rng.random_range(0.0..1.0),
                     };
                 }
                 2 | _ => {
@@ -1009,25 +1611,76 @@ impl Brain {
                         weight: rng.random_range(-1.0..1.0),
                         bias: rng.random_range(-1.0..1.0),
                         enabled: true,
+                        innovation: 0,
+                        dominance: rng.random_range(0.0..1.0),
                     };
                 }
             }
-            mutated_brain.connections.push(new_connection);
+            // A connection that already exists would duplicate its innovation
+            if let (Some(from), Some(to)) = (
+                mutated_brain.neuron_key(new_connection.from_id),
+                mutated_brain.neuron_key(new_connection.to_id),
+            ) {
+                new_connection.innovation = innovations.connection(from, to);
+                if !mutated_brain
+                    .connections
+                    .iter()
+                    .any(|c| c.innovation == new_connection.innovation)
+                {
+                    mutated_brain.connections.push(new_connection);
+                }
+            }
+        }
+
+        // insertion of eye, in a free slot
+        if rng.random_range(0.0..1.) < insertion_chance {
+            let free_slots: Vec<u8> = (0..MAX_EYES).filter(|&s| !mutated_brain.has_eye(s)).collect();
+            if let Some(&slot) = free_slots.choose(rng) {
+                mutated_brain.eyes.push(Eye {
+                    slot,
+                    angle: rng.random_range(-PI..PI),
+                    range: rng.random_range(EYE_MIN_RANGE..EYE_MAX_RANGE),
+                });
+            }
         }
 
         // Deletion mutations
-        // same as insertion
+        if rng.random_range(0.0..1.) < deletion_chance && !mutated_brain.eyes.is_empty() {
+            let slot = mutated_brain.eyes[rng.random_range(0..mutated_brain.eyes.len())].slot;
+            // Inputs can't run empty, so an eye feeding every input stays
+            if mutated_brain.inputs.iter().any(|n| n.neuron_type.eye_slot() != Some(slot)) {
+                mutated_brain.remove_eye(slot);
+            }
+        }
+        if rng.random_range(0.0..1.) < deletion_chance && !mutated_brain.connections.is_empty() {
+            let idx = rng.random_range(0..mutated_brain.connections.len());
+            mutated_brain.connections.remove(idx);
+        }
+        if rng.random_range(0.0..1.) < deletion_chance {
+            // Each layer keeps at least one neuron, since mutations pick random neurons from them
+            match rng.random_range(0..2) {
+                0 if mutated_brain.hidden_layers.len() > 1 => {
+                    let idx = rng.random_range(0..mutated_brain.hidden_layers.len());
+                    mutated_brain.remove_neuron(idx + 100);
+                }
+                1 if mutated_brain.inputs.len() > 1 => {
+                    let idx = rng.random_range(0..mutated_brain.inputs.len());
+                    mutated_brain.remove_neuron(idx);
+                }
+                _ => {}
+            }
+        }
 
         for connection in mutated_brain.connections.iter_mut() {
             // Mutate the weight
             if rng.random_range(0.0..1.) < mutation_chance {
-                let change = rng.random_range(-mutation_amount..mutation_amount);
+                let change = rng.random_range(-mutation_amount..=mutation_amount);
                 connection.weight += change;
             }
 
             // Mutate the bias
             if rng.random_range(0.0..1.) < mutation_chance {
-                let change = rng.random_range(-mutation_amount..mutation_amount);
+                let change = rng.random_range(-mutation_amount..=mutation_amount);
                 connection.bias += change;
             }
 
@@ -1035,19 +1688,184 @@ impl Brain {
             if rng.random_range(0.0..1.) < mutation_chance {
                 connection.enabled = !connection.enabled;
             }
+
+            // Mutate the dominance
+            if rng.random_range(0.0..1.) < mutation_chance {
+                let change = rng.random_range(-mutation_amount..=mutation_amount);
+                connection.dominance = (connection.dominance + change).clamp(0.0, 1.0);
+            }
+        }
+
+        for eye in mutated_brain.eyes.iter_mut() {
+            if rng.random_range(0.0..1.) < mutation_chance {
+                let change = rng.random_range(-mutation_amount..=mutation_amount);
+                eye.angle = (eye.angle + change + PI).rem_euclid(2.0 * PI) - PI;
+            }
+            if rng.random_range(0.0..1.) < mutation_chance {
+                let change = rng.random_range(-mutation_amount..=mutation_amount);
+                eye.range = (eye.range * (1.0 + change)).clamp(EYE_MIN_RANGE, EYE_MAX_RANGE);
+            }
         }
 
         // Optionally, mutate neurons (e.g., activation functions)
         for neuron in mutated_brain.hidden_layers.iter_mut() {
             if rng.random_range(0.0..1.) < mutation_chance {
-                neuron.activation_function = ActivationFunction::random();
+                neuron.activation_function = ActivationFunction::random(rng);
             }
         }
 
+        // Crossover ends in a mutation too, so this covers both. An invalid child is a bug in the
+        // mutation code: debug builds stop on it, release builds fall back to the parent.
+        let validation = mutated_brain.validate();
+        debug_assert_eq!(validation, Ok(()), "mutation produced an invalid brain");
+        if let Err(e) = validation {
+            warn!("mutation produced an invalid brain, keeping the parent's: {e}");
+            return self.clone();
+        }
+
         mutated_brain
     }
 }
 
+/// `Brain` flattened for evaluation: enabled connections grouped by target neuron (CSR) and
+/// preallocated value buffers. Built when a craber spawns; any change to the brain's
+/// structure, weights or activations needs a fresh `Brain::compile`.
+/// Neurons are indexed densely: inputs, then hidden, then outputs.
+#[derive(Component, Clone, Debug, Default)]
+pub struct CompiledBrain {
+    input_count: usize,
+    hidden_count: usize,
+    /// Incoming connections of hidden and output neuron `row` are
+    /// `row_offsets[row]..row_offsets[row + 1]`, in the order they appear in `Brain::connections`.
+    row_offsets: Vec<u32>,
+    sources: Vec<u32>,
+    weights: Vec<f32>,
+    biases: Vec<f32>,
+    /// Activation of every hidden and output neuron, by row.
+    activations: Vec<ActivationFunction>,
+    /// `Brain::hidden_evaluation_order`.
+    hidden_order: Vec<u32>,
+    values: Vec<f32>,
+    prev: Vec<f32>,
+}
+
+impl Brain {
+    pub fn compile(&self) -> CompiledBrain {
+        let (input_count, hidden_count) = (self.inputs.len(), self.hidden_layers.len());
+        let dense = |id: usize| -> Option<usize> {
+            self.get_neuron(id)?;
+            Some(if id < 100 {
+                id
+            } else if id < 200 {
+                input_count + id - 100
+            } else {
+                input_count + hidden_count + id - 200
+            })
+        };
+        let rows = hidden_count + self.outputs.len();
+        // Connections into inputs are never read, dangling ones are dropped
+        let edges: Vec<(usize, usize, &Connection)> = self
+            .connections
+            .iter()
+            .filter(|c| c.enabled && c.to_id >= 100)
+            .filter_map(|c| Some((dense(c.from_id)?, dense(c.to_id)? - input_count, c)))
+            .collect();
+
+        // Stable counting sort by target keeps each row's connections in their original order,
+        // so sums are accumulated exactly like `Brain::feed_forward` does
+        let mut row_offsets = vec![0u32; rows + 1];
+        for &(_, row, _) in &edges {
+            row_offsets[row + 1] += 1;
+        }
+        for row in 0..rows {
+            row_offsets[row + 1] += row_offsets[row];
+        }
+        let mut next: Vec<u32> = row_offsets[..rows].to_vec();
+        let mut sources = vec![0u32; edges.len()];
+        let mut weights = vec![0.0f32; edges.len()];
+        let mut biases = vec![0.0f32; edges.len()];
+        for &(source, row, conn) in &edges {
+            let slot = next[row] as usize;
+            next[row] += 1;
+            sources[slot] = source as u32;
+            weights[slot] = conn.weight;
+            biases[slot] = conn.bias;
+        }
+
+        let neuron_count = input_count + rows;
+        CompiledBrain {
+            input_count,
+            hidden_count,
+            row_offsets,
+            sources,
+            weights,
+            biases,
+            activations: self
+                .hidden_layers
+                .iter()
+                .chain(self.outputs.iter())
+                .map(|n| n.activation_function)
+                .collect(),
+            hidden_order: self.hidden_evaluation_order().into_iter().map(|i| i as u32).collect(),
+            values: vec![0.0; neuron_count],
+            prev: vec![0.0; neuron_count],
+        }
+    }
+}
+
+impl CompiledBrain {
+    /// Runs one brain tick on `brain`, which must be the brain this was compiled from.
+    /// Gives the same results as `Brain::feed_forward`.
+    pub fn feed_forward(&mut self, brain: &mut Brain, evaluation: BrainEvaluation, relaxation_steps: u32) {
+        let neurons = brain
+            .inputs
+            .iter()
+            .chain(brain.hidden_layers.iter())
+            .chain(brain.outputs.iter());
+        for (value, neuron) in self.values.iter_mut().zip(neurons) {
+            *value = neuron.value;
+        }
+
+        match evaluation {
+            BrainEvaluation::Relaxation => {
+                for _ in 0..relaxation_steps.max(1) {
+                    self.prev.copy_from_slice(&self.values);
+                    for row in 0..self.activations.len() {
+                        self.values[self.input_count + row] = self.evaluate_row(row, &self.prev);
+                    }
+                }
+            }
+            BrainEvaluation::Topological => {
+                for i in 0..self.hidden_order.len() {
+                    let row = self.hidden_order[i] as usize;
+                    self.values[self.input_count + row] = self.evaluate_row(row, &self.values);
+                }
+                for row in self.hidden_count..self.activations.len() {
+                    self.values[self.input_count + row] = self.evaluate_row(row, &self.values);
+                }
+            }
+        }
+
+        let hidden_start = self.input_count;
+        let output_start = hidden_start + self.hidden_count;
+        for (neuron, value) in brain.hidden_layers.iter_mut().zip(&self.values[hidden_start..output_start]) {
+            neuron.value = *value;
+        }
+        for (neuron, value) in brain.outputs.iter_mut().zip(&self.values[output_start..]) {
+            neuron.value = *value;
+        }
+    }
+
+    fn evaluate_row(&self, row: usize, values: &[f32]) -> f32 {
+        let (start, end) = (self.row_offsets[row] as usize, self.row_offsets[row + 1] as usize);
+        let mut sum = 0.0f32;
+        for i in start..end {
+            sum += values[self.sources[i] as usize] * self.weights[i] + self.biases[i];
+        }
+        finite_clamp(self.activations[row].calculate(sum), -1e6, 1e6)
+    }
+}
+
 #[derive(Component)]
 pub struct Vision {
     pub radius: f32,
@@ -1061,7 +1879,12 @@ pub struct Vision {
     pub see_food: bool,
     pub see_craber: bool,
     pub see_wall: bool,
-    pub entities_in_vision: Vec<Entity>,
+    /// Every food in range, refreshed whenever the brain ticks.
+    pub visible_food: Vec<Entity>,
+    /// Every other craber in range, refreshed whenever the brain ticks.
+    pub visible_crabers: Vec<Entity>,
+    /// First hit of each of the brain's eyes, by slot.
+    pub eye_hits: [Option<EyeHit>; MAX_EYES as usize],
     pub food_seen_timer: f32,
     pub craber_seen_timer: f32,
     pub wall_seen_timer: f32,
@@ -1072,14 +1895,14 @@ impl Vision {
         self.see_food = false;
         self.nearest_food_distance = std::f32::MAX;
         self.nearest_food_direction = 0.;
-        self.entities_in_vision = Vec::new();
+        self.visible_food.clear();
     }
     pub fn no_see_craber(&mut self) {
         self.see_craber = false;
         self.nearest_craber_distance = std::f32::MAX;
         self.nearest_craber_direction = 0.;
         self.nearest_craber_genetic_closeness = 0.;
-        self.entities_in_vision = Vec::new();
+        self.visible_crabers.clear();
     }
     pub fn no_see_wall(&mut self) {
         self.see_wall = false;
@@ -1087,3 +1910,269 @@ impl Vision {
         self.nearest_wall_direction = 0.;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn neuron(neuron_type: NeuronType) -> Neuron {
+        Neuron {
+            neuron_type,
+            activation_function: ActivationFunction::None,
+            value: if neuron_type == NeuronType::AlwaysOn { 1.0 } else { 0.0 },
+            innovation: 0,
+        }
+    }
+
+    fn connection(from_id: usize, to_id: usize) -> Connection {
+        Connection {
+            from_id,
+            to_id,
+            weight: 1.0,
+            bias: 0.0,
+            enabled: true,
+            innovation: 0,
+            dominance: 0.5,
+        }
+    }
+
+    /// AlwaysOn input, `hidden` linear hidden neurons and a KickStrength output.
+    fn brain(hidden: usize, connections: &[(usize, usize)]) -> Brain {
+        Brain {
+            inputs: vec![neuron(NeuronType::AlwaysOn)],
+            hidden_layers: (0..hidden).map(|_| neuron(NeuronType::Hidden)).collect(),
+            outputs: vec![neuron(NeuronType::KickStrength)],
+            connections: connections.iter().map(|&(from, to)| connection(from, to)).collect(),
+            eyes: Vec::new(),
+        }
+    }
+
+    /// Brain ticks until the output first reads non-zero.
+    fn output_latency(mut brain: Brain, evaluation: BrainEvaluation, relaxation_steps: u32) -> usize {
+        for tick in 1..=10 {
+            brain.feed_forward(evaluation, relaxation_steps);
+            if brain.get_kick_strength() != 0.0 {
+                return tick;
+            }
+        }
+        panic!("output never changed");
+    }
+
+    #[test]
+    fn relaxation_adds_one_tick_per_connection() {
+        assert_eq!(output_latency(brain(1, &[(0, 100), (100, 200)]), BrainEvaluation::Relaxation, 1), 2);
+        let chain = brain(3, &[(0, 100), (100, 101), (101, 102), (102, 200)]);
+        assert_eq!(output_latency(chain, BrainEvaluation::Relaxation, 1), 4);
+    }
+
+    #[test]
+    fn relaxation_steps_shorten_latency() {
+        let chain = brain(3, &[(0, 100), (100, 101), (101, 102), (102, 200)]);
+        assert_eq!(output_latency(chain.clone(), BrainEvaluation::Relaxation, 2), 2);
+        assert_eq!(output_latency(chain, BrainEvaluation::Relaxation, 4), 1);
+    }
+
+    #[test]
+    fn topological_reaches_outputs_in_one_tick() {
+        assert_eq!(output_latency(brain(1, &[(0, 100), (100, 200)]), BrainEvaluation::Topological, 1), 1);
+        // Hidden ids run against the direction of the chain
+        let chain = brain(3, &[(0, 102), (102, 101), (101, 100), (100, 200)]);
+        assert_eq!(output_latency(chain, BrainEvaluation::Topological, 1), 1);
+    }
+
+    #[test]
+    fn topological_reads_recurrent_connections_from_last_tick() {
+        // 100 -> 101 -> 100 is a cycle, broken at 100, so 101 -> 100 is the recurrent edge
+        let mut cycle = brain(2, &[(0, 101), (100, 101), (101, 100), (100, 200)]);
+        assert_eq!(cycle.hidden_evaluation_order(), vec![0, 1]);
+        cycle.feed_forward(BrainEvaluation::Topological, 1);
+        assert_eq!(cycle.hidden_layers[0].value, 0.0);
+        assert_eq!(cycle.hidden_layers[1].value, 1.0);
+        assert_eq!(cycle.get_kick_strength(), 0.0);
+        cycle.feed_forward(BrainEvaluation::Topological, 1);
+        assert_eq!(cycle.hidden_layers[0].value, 1.0);
+        assert_eq!(cycle.hidden_layers[1].value, 2.0);
+        assert_eq!(cycle.get_kick_strength(), 1.0);
+    }
+
+    #[test]
+    fn self_connections_read_last_tick() {
+        let mut brain = brain(1, &[(0, 100), (100, 100), (100, 200)]);
+        brain.feed_forward(BrainEvaluation::Topological, 1);
+        assert_eq!(brain.hidden_layers[0].value, 1.0);
+        brain.feed_forward(BrainEvaluation::Topological, 1);
+        assert_eq!(brain.hidden_layers[0].value, 2.0);
+    }
+
+    #[test]
+    fn compiled_brain_matches_reference_evaluator() {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let mut innovations = InnovationRegistry::default();
+        let mut brain = Brain::default();
+        for _ in 0..200 {
+            brain = brain.new_mutated_brain(0.5, 1.0, 0.5, 0.1, &mut innovations, &mut rng);
+            for (evaluation, steps) in [
+                (BrainEvaluation::Relaxation, 1),
+                (BrainEvaluation::Relaxation, 3),
+                (BrainEvaluation::Topological, 1),
+            ] {
+                let mut reference = brain.clone();
+                let mut compiled_brain = brain.clone();
+                let mut compiled = compiled_brain.compile();
+                for tick in 0..5 {
+                    for input_type in reference.get_input_types() {
+                        let value = (tick as f32 * 0.37).sin();
+                        reference.update_input(input_type, value);
+                        compiled_brain.update_input(input_type, value);
+                    }
+                    reference.feed_forward(evaluation, steps);
+                    compiled.feed_forward(&mut compiled_brain, evaluation, steps);
+                    let bits = |b: &Brain| -> Vec<u32> {
+                        b.hidden_layers.iter().chain(&b.outputs).map(|n| n.value.to_bits()).collect()
+                    };
+                    assert_eq!(bits(&reference), bits(&compiled_brain));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mutation_and_crossover_keep_hidden_neurons_in_their_id_range() {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(5);
+        let mut innovations = InnovationRegistry::default();
+        let (mut a, mut b) = (Brain::default(), Brain::default());
+        for _ in 0..600 {
+            a = a.new_mutated_brain(0.0, 0.5, 1.0, 0.0, &mut innovations, &mut rng);
+            b = b.new_mutated_brain(0.0, 0.5, 1.0, 0.0, &mut innovations, &mut rng);
+        }
+        assert_eq!(a.hidden_layers.len(), 100);
+        let child = a.crossover_brain(&b, 0.0, 0.5, 1.0, 0.0, &mut innovations, &mut rng);
+        for brain in [&a, &b, &child] {
+            assert_eq!(brain.validate(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn heavy_insertion_and_deletion_always_produce_valid_children() {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(11);
+        let mut innovations = InnovationRegistry::default();
+        let mut lineages = vec![Brain::default(); 4];
+        for round in 0..500 {
+            for i in 0..lineages.len() {
+                let child = if round % 5 == 4 {
+                    let other = &lineages[(i + 1) % lineages.len()];
+                    lineages[i].crossover_brain(other, 0.5, 0.5, 0.9, 0.9, &mut innovations, &mut rng)
+                } else {
+                    lineages[i].new_mutated_brain(0.5, 0.5, 0.9, 0.9, &mut innovations, &mut rng)
+                };
+                assert_eq!(child.validate(), Ok(()), "round {round}, lineage {i}");
+                lineages[i] = child;
+            }
+        }
+    }
+
+    #[test]
+    fn mutation_amount_of_zero_leaves_weights_alone() {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(2);
+        let mut innovations = InnovationRegistry::default();
+        let brain = Brain::default();
+        let mutated = brain.new_mutated_brain(1.0, 0.0, 0.0, 0.0, &mut innovations, &mut rng);
+        let weights = |brain: &Brain| brain.connections.iter().map(|c| c.weight).collect::<Vec<_>>();
+        assert_eq!(weights(&mutated), weights(&brain));
+    }
+
+    #[test]
+    fn removing_an_eye_removes_its_inputs() {
+        let mut brain = brain(1, &[(0, 200), (1, 100), (100, 200)]);
+        brain.eyes.push(Eye {
+            slot: 2,
+            angle: 0.0,
+            range: 100.0,
+        });
+        brain.inputs.push(neuron(NeuronType::EyeFood(2)));
+        assert_eq!(brain.validate(), Ok(()));
+
+        brain.remove_eye(2);
+        assert_eq!(brain.validate(), Ok(()));
+        assert_eq!(brain.get_input_types(), vec![NeuronType::AlwaysOn]);
+        assert_eq!(brain.connections.len(), 2);
+
+        // An eye input needs its eye
+        brain.inputs.push(neuron(NeuronType::EyeFood(2)));
+        assert!(brain.validate().is_err());
+    }
+
+    #[test]
+    fn expression_blends_shared_genes_and_hides_recessive_ones() {
+        let gene = |from_id, to_id, weight, innovation, dominance| Connection {
+            from_id,
+            to_id,
+            weight,
+            bias: 0.0,
+            enabled: true,
+            innovation,
+            dominance,
+        };
+        // Both carry 0 -> 200, a dominantly. Only a carries a recessive 0 -> 100, only b a
+        // dominant 100 -> 200
+        let mut a = brain(1, &[]);
+        a.connections = vec![gene(0, 200, 1.0, 1, 0.75), gene(0, 100, 1.0, 2, 0.25)];
+        let mut b = brain(1, &[]);
+        b.connections = vec![gene(0, 200, -1.0, 1, 0.25), gene(100, 200, 1.0, 3, 0.5)];
+
+        let phenotype = a.express(&b);
+        assert_eq!(phenotype.validate(), Ok(()));
+        let expressed: Vec<u64> = phenotype.connections.iter().map(|c| c.innovation).collect();
+        assert_eq!(expressed, vec![1, 3]);
+        assert_eq!(phenotype.connections[0].weight, 0.5);
+    }
+
+    #[test]
+    fn gene_transfer_copies_a_connected_subgraph_with_its_neurons() {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let gene = |from_id, to_id, innovation| Connection {
+            innovation,
+            ..connection(from_id, to_id)
+        };
+        // The donor sees food with an eye feeding its own hidden neuron, 1 -> 100 -> 200
+        let mut donor = brain(1, &[]);
+        donor.hidden_layers[0].innovation = 7;
+        donor.eyes.push(Eye {
+            slot: 1,
+            angle: 0.5,
+            range: 100.0,
+        });
+        donor.inputs.push(neuron(NeuronType::EyeFood(1)));
+        donor.connections = vec![gene(0, 200, 1), gene(1, 100, 10), gene(100, 200, 11)];
+        let mut recipient = brain(1, &[]);
+        recipient.connections = vec![gene(0, 200, 1)];
+
+        assert_eq!(recipient.receive_genes(&donor, 5, &mut rng), 2);
+        assert_eq!(recipient.validate(), Ok(()));
+        assert!(recipient.has_eye(1));
+        assert_eq!(recipient.hidden_layers.len(), 2);
+        let copied: Vec<(usize, usize)> = recipient.connections[1..].iter().map(|c| (c.from_id, c.to_id)).collect();
+        assert!(copied.contains(&(1, 101)) && copied.contains(&(101, 200)));
+
+        // Nothing left to copy
+        assert_eq!(recipient.receive_genes(&donor, 5, &mut rng), 0);
+    }
+
+    #[test]
+    fn default_brain_rotates_one_tick_after_seeing_food_when_topological() {
+        let mut brain = Brain::default();
+        brain.update_input(NeuronType::NearestFoodAngle, 0.5);
+        brain.feed_forward(BrainEvaluation::Topological, 1);
+        assert!(brain.get_rotation() != 0.0);
+
+        let mut brain = Brain::default();
+        brain.update_input(NeuronType::NearestFoodAngle, 0.5);
+        brain.feed_forward(BrainEvaluation::Relaxation, 1);
+        assert_eq!(brain.get_rotation(), 0.0);
+    }
+}
@@ -0,0 +1,68 @@
+use crate::config::SimConfig;
+
+pub const USAGE: &str = "usage: crabers [--seed N] [--config FILE.ron] [--set NAME=VALUE]... \
+[--print-config] [--headless [--seconds N] [--ticks N]]";
+
+/// Command line options.
+#[derive(Default)]
+pub struct CliArgs {
+    pub headless: bool,
+    pub seconds: Option<f64>,
+    pub ticks: Option<u64>,
+    /// Seed for all simulation randomness; a random one is picked (and logged) when absent.
+    pub seed: Option<u64>,
+    pub config_path: Option<String>,
+    /// `SimConfig` field overrides, applied in order after the config file.
+    pub overrides: Vec<(String, String)>,
+    /// Print the resulting `SimConfig` as RON and exit.
+    pub print_config: bool,
+}
+
+impl CliArgs {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut cli = CliArgs::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => cli.headless = true,
+                "--seconds" => cli.seconds = Some(parse_value(&arg, args.next())?),
+                "--ticks" => cli.ticks = Some(parse_value(&arg, args.next())?),
+                "--seed" => cli.seed = Some(parse_value(&arg, args.next())?),
+                "--config" => cli.config_path = Some(parse_value(&arg, args.next())?),
+                "--set" => {
+                    let assignment: String = parse_value(&arg, args.next())?;
+                    let (name, value) = assignment
+                        .split_once('=')
+                        .ok_or_else(|| format!("--set expects NAME=VALUE, got: {assignment}"))?;
+                    cli.overrides.push((name.to_string(), value.to_string()));
+                }
+                "--print-config" => cli.print_config = true,
+                _ => return Err(format!("unknown argument: {arg}")),
+            }
+        }
+        if !cli.headless && (cli.seconds.is_some() || cli.ticks.is_some()) {
+            return Err("--seconds and --ticks require --headless".to_string());
+        }
+        Ok(cli)
+    }
+
+    /// Builds the `SimConfig` from the defaults, the config file and the `--set` overrides.
+    pub fn sim_config(&self) -> Result<SimConfig, String> {
+        let mut config = match &self.config_path {
+            Some(path) => SimConfig::read_from_file(path)?,
+            None => SimConfig::default(),
+        };
+        for (name, value) in &self.overrides {
+            config.set(name, value)?;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    let value = value.ok_or_else(|| format!("{flag} needs a value"))?;
+    value
+        .parse()
+        .map_err(|_| format!("invalid {flag} value: {value}"))
+}
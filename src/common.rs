@@ -1,28 +1,21 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use std::f32::consts::PI;
 
 use avian2d::prelude::*;
 
+use crate::lineage::CauseOfDeath;
+
 // Define the collision layers
 #[derive(PhysicsLayer, Default)]
 pub enum Layer {
     #[default]
     Craber,
     Food,
-    Vision,
     Wall,
 }
 
-// Constants for debug build
-#[cfg(not(target_arch = "wasm32"))]
-pub const WORLD_SIZE: f32 = 10000.0;
-
-#[cfg(target_arch = "wasm32")]
-pub const WORLD_SIZE: f32 = 10000.0;
-
-pub const WALL_THICKNESS: f32 = 60.0;
-
 #[derive(Message)]
 pub struct DespawnEvent {
     pub entity: Entity,
@@ -37,6 +30,7 @@ pub struct FoodSpawnEvent {
 #[derive(Message)]
 pub struct CraberDespawnEvent {
     pub entity: Entity,
+    pub cause: CauseOfDeath,
 }
 
 #[derive(Resource)]
@@ -71,6 +65,44 @@ pub struct SelectedEntity {
     pub brain_info: String,
 }
 
+/// Energy held by neither crabers nor food. Everything crabers spend flows back into it, and new
+/// food and randomly spawned crabers are paid out of it, so pool + food + crabers stays `total`.
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+pub struct EnergyPool {
+    pub energy: f64,
+    /// Energy in the whole world, fixed when the world is created.
+    pub total: f64,
+}
+
+impl EnergyPool {
+    pub fn new(total: f32) -> Self {
+        Self {
+            energy: total as f64,
+            total: total as f64,
+        }
+    }
+
+    /// Takes `amount` out of the pool, unless it holds less than that. Negative or non-finite
+    /// amounts are refused, they would add energy instead.
+    pub fn withdraw(&mut self, amount: f64) -> bool {
+        if !amount.is_finite() || amount < 0.0 || self.energy < amount {
+            return false;
+        }
+        self.energy -= amount;
+        true
+    }
+
+    /// Puts `amount` into the pool. Negative or non-finite amounts are refused.
+    pub fn deposit(&mut self, amount: f64) -> bool {
+        if !amount.is_finite() || amount < 0.0 {
+            warn!("EnergyPool: refused to deposit {amount}");
+            return false;
+        }
+        self.energy += amount;
+        true
+    }
+}
+
 #[derive(Resource, Default)]
 pub struct DebugInfo {
     pub fps: f64,
@@ -79,7 +111,7 @@ pub struct DebugInfo {
     pub food_count: usize,
 }
 
-#[derive(Resource)]
+#[derive(Resource, Clone, Serialize, Deserialize)]
 pub struct SimulationStats {
     pub craber_history: VecDeque<[f64; 2]>,
     pub food_history: VecDeque<[f64; 2]>,
@@ -122,10 +154,21 @@ pub struct SimulationStats {
     pub death_rate_history: VecDeque<[f64; 2]>,
     pub sexual_birth_rate_history: VecDeque<[f64; 2]>,
     pub asexual_birth_rate_history: VecDeque<[f64; 2]>,
+    pub pool_energy_history: VecDeque<[f64; 2]>,
+    pub food_energy_history: VecDeque<[f64; 2]>,
+    pub craber_energy_history: VecDeque<[f64; 2]>,
+    pub total_energy_history: VecDeque<[f64; 2]>,
+    pub gene_transfer_rate_history: VecDeque<[f64; 2]>,
+    pub transferred_connections_history: VecDeque<[f64; 2]>,
     pub birth_counter: u32,
     pub death_counter: u32,
     pub sexual_birth_counter: u32,
     pub asexual_birth_counter: u32,
+    pub gene_transfer_counter: u32,
+    pub transferred_connection_counter: u32,
+    /// Gene transfers and connections they copied over the whole run.
+    pub gene_transfer_total: u64,
+    pub transferred_connection_total: u64,
     pub sample_timer: Timer,
     pub capacity: usize,
 }
@@ -174,10 +217,20 @@ impl SimulationStats {
             death_rate_history: VecDeque::with_capacity(capacity),
             sexual_birth_rate_history: VecDeque::with_capacity(capacity),
             asexual_birth_rate_history: VecDeque::with_capacity(capacity),
+            pool_energy_history: VecDeque::with_capacity(capacity),
+            food_energy_history: VecDeque::with_capacity(capacity),
+            craber_energy_history: VecDeque::with_capacity(capacity),
+            total_energy_history: VecDeque::with_capacity(capacity),
+            gene_transfer_rate_history: VecDeque::with_capacity(capacity),
+            transferred_connections_history: VecDeque::with_capacity(capacity),
             birth_counter: 0,
             death_counter: 0,
             sexual_birth_counter: 0,
             asexual_birth_counter: 0,
+            gene_transfer_counter: 0,
+            transferred_connection_counter: 0,
+            gene_transfer_total: 0,
+            transferred_connection_total: 0,
             sample_timer: Timer::from_seconds(1.0, TimerMode::Repeating),
             capacity,
         }
@@ -196,26 +249,11 @@ pub fn collides(a: &Transform, b: &Transform, collision_threshold: f32) -> bool
     distance.length() < collision_threshold
 }
 
-// Movement constants
-pub const MAX_IMPULSE: f32 = 200.0;
-pub const KICK_THRESHOLD: f32 = 0.01;
-pub const KICK_ENERGY_MODIFIER: f32 = 2.0;
-
-// Quadratic water drag constants
-pub const LINEAR_DRAG_COEFFICIENT: f32 = 0.01;
-pub const ANGULAR_DRAG_COEFFICIENT: f32 = 1.0;
-pub const KICK_STEEPNESS: f32 = 0.5;
-pub const KICK_RATE_STEEPNESS: f32 = 0.5;
-
-// Rotation constants
-pub const ROTATION_THRESHOLD: f32 = 0.01;
-pub const ROTATION_RATE_STEEPNESS: f32 = 0.5;
-pub const MAX_ANGULAR_IMPULSE: f32 = 0.1;
-
-// Brain tick constants
-pub const BRAIN_TICK_MIN_RATE: f32 = 1.0; // min ticks per second (Hz)
-pub const BRAIN_TICK_MAX_RATE: f32 = 30.0; // max ticks per second (Hz)
-pub const BRAIN_TICK_ENERGY_COST: f32 = 0.05; // energy per tick
+/// Static arena wall, an axis-aligned rectangle around its transform.
+#[derive(Component)]
+pub struct Wall {
+    pub half_size: Vec2,
+}
 
 #[derive(Component, Debug, Clone, Copy, PartialEq)]
 pub enum EntityType {
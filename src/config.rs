@@ -0,0 +1,335 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::brain::BrainEvaluation;
+
+/// Tunable simulation parameters. Loaded from a RON file (`--config`) and/or overridden per
+/// field on the command line (`--set name=value`); fields missing from the file keep their defaults.
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimConfig {
+    // World (read once at startup)
+    pub world_size: f32,
+    pub wall_thickness: f32,
+    pub gravity: f32,
+    /// Energy in the world, shared by the energy pool, food and crabers.
+    pub world_energy: f32,
+
+    // Food
+    /// Seconds between food spawns.
+    pub food_spawn_rate: f32,
+    pub max_food_count: usize,
+    pub food_size: f32,
+    pub food_energy_min: f32,
+    pub food_energy_max: f32,
+
+    // Craber spawning and body
+    /// Seconds between random craber spawns.
+    pub craber_spawn_rate: f32,
+    pub max_crabers: usize,
+    /// Random spawns stop once the population reaches this size.
+    pub max_crabers_spawner: usize,
+    pub craber_spawn_multiplier: usize,
+    pub craber_size: f32,
+    pub craber_mass: f32,
+    pub craber_inertia: f32,
+
+    // Energy and health
+    pub energy_consumption_rate: f32,
+    pub healing_rate: f32,
+    pub healing_cost: f32,
+    /// Share of a dead craber's energy that is dropped as food.
+    pub death_energy_factor: f32,
+    pub required_reproduce_energy: f32,
+    pub reproduce_energy: f32,
+
+    // Mutation
+    pub mutation_chance: f32,
+    pub mutation_amount: f32,
+    pub insertion_chance: f32,
+    pub deletion_chance: f32,
+
+    // Body genes
+    /// Chance for each body gene to mutate in a child.
+    pub gene_mutation_chance: f32,
+    /// Largest relative change of a mutated body gene.
+    pub gene_mutation_amount: f32,
+    /// Energy per second the default body costs, scaled by `Genome::upkeep`.
+    pub body_upkeep_cost: f32,
+
+    // Horizontal gene transfer
+    /// Touching crabers swap genes when both `WantGeneTransfer` outputs are above this.
+    pub gene_transfer_threshold: f32,
+    /// Most connection genes one transfer copies.
+    pub gene_transfer_max_connections: usize,
+    /// Energy the recipient pays per transfer.
+    pub gene_transfer_cost: f32,
+    /// Seconds before a craber can receive genes again.
+    pub gene_transfer_cooldown: f32,
+
+    // Speciation
+    /// Crabers whose compatibility distance (1 - genetic closeness) to a species' representative
+    /// is below this belong to that species.
+    pub species_compatibility_threshold: f32,
+    /// Seconds between speciation passes.
+    pub speciation_interval: f32,
+
+    // Movement
+    pub max_impulse: f32,
+    pub kick_threshold: f32,
+    pub kick_energy_modifier: f32,
+    pub kick_steepness: f32,
+    pub kick_rate_steepness: f32,
+    pub rotation_threshold: f32,
+    pub rotation_rate_steepness: f32,
+    pub max_angular_impulse: f32,
+
+    // Quadratic water drag
+    pub linear_drag_coefficient: f32,
+    pub angular_drag_coefficient: f32,
+
+    // Brain
+    /// Min brain ticks per second (Hz).
+    pub brain_tick_min_rate: f32,
+    /// Max brain ticks per second (Hz).
+    pub brain_tick_max_rate: f32,
+    /// Energy spent per brain tick.
+    pub brain_tick_energy_cost: f32,
+    pub brain_evaluation: BrainEvaluation,
+    /// Propagation steps per brain tick in `BrainEvaluation::Relaxation` mode.
+    pub brain_relaxation_steps: u32,
+    /// How long (seconds) a sighting stays in the brain's inputs after losing sight of it.
+    pub vision_update_rate: f32,
+
+    // Pheromones
+    /// Side of a pheromone grid cell (read once at startup).
+    pub pheromone_cell_size: f32,
+    /// Share of the difference to each neighbouring cell that flows over per second.
+    pub pheromone_diffusion_rate: f32,
+    /// Share of the pheromone that evaporates per second.
+    pub pheromone_evaporation_rate: f32,
+    /// Pheromone deposited per second at full emission.
+    pub pheromone_emission_rate: f32,
+    /// Energy spent per unit of pheromone deposited.
+    pub pheromone_energy_cost: f32,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            world_size: 10000.0,
+            wall_thickness: 60.0,
+            gravity: 0.0,
+            world_energy: 150_000.0,
+
+            food_spawn_rate: 0.0004,
+            max_food_count: 10000,
+            food_size: 10.0,
+            food_energy_min: 5.0,
+            food_energy_max: 15.0,
+
+            craber_spawn_rate: 0.1,
+            max_crabers: 5000,
+            max_crabers_spawner: 20,
+            craber_spawn_multiplier: 1,
+            craber_size: 10.0,
+            craber_mass: 0.5,
+            craber_inertia: 0.05,
+
+            energy_consumption_rate: 0.03,
+            healing_rate: 0.05,
+            healing_cost: 1.3,
+            death_energy_factor: 0.7,
+            required_reproduce_energy: 100.0,
+            reproduce_energy: 60.0,
+
+            mutation_chance: 0.05,
+            mutation_amount: 0.5,
+            insertion_chance: 0.05,
+            deletion_chance: 0.05,
+
+            gene_mutation_chance: 0.1,
+            gene_mutation_amount: 0.1,
+            body_upkeep_cost: 0.2,
+
+            gene_transfer_threshold: 0.5,
+            gene_transfer_max_connections: 4,
+            gene_transfer_cost: 5.0,
+            gene_transfer_cooldown: 5.0,
+
+            species_compatibility_threshold: 0.3,
+            speciation_interval: 1.0,
+
+            max_impulse: 200.0,
+            kick_threshold: 0.01,
+            kick_energy_modifier: 2.0,
+            kick_steepness: 0.5,
+            kick_rate_steepness: 0.5,
+            rotation_threshold: 0.01,
+            rotation_rate_steepness: 0.5,
+            max_angular_impulse: 0.1,
+
+            linear_drag_coefficient: 0.01,
+            angular_drag_coefficient: 1.0,
+
+            brain_tick_min_rate: 1.0,
+            brain_tick_max_rate: 30.0,
+            brain_tick_energy_cost: 0.05,
+            brain_evaluation: BrainEvaluation::Relaxation,
+            brain_relaxation_steps: 1,
+            vision_update_rate: 0.01,
+
+            pheromone_cell_size: 100.0,
+            pheromone_diffusion_rate: 1.0,
+            pheromone_evaporation_rate: 0.1,
+            pheromone_emission_rate: 5.0,
+            pheromone_energy_cost: 0.02,
+        }
+    }
+}
+
+impl SimConfig {
+    pub fn from_ron_str(text: &str) -> Result<Self, String> {
+        ron::from_str(text).map_err(|e| format!("failed to parse config: {e}"))
+    }
+
+    pub fn read_from_file(path: &str) -> Result<Self, String> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| format!("failed to read {path}: {e}"))?;
+        let config = Self::from_ron_str(&text).map_err(|e| format!("{path}: {e}"))?;
+        config.validate().map_err(|e| format!("{path}: {e}"))?;
+        Ok(config)
+    }
+
+    /// Rejects values the simulation can't run with, e.g. empty food energy ranges or spawn
+    /// intervals that aren't positive.
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value) in [
+            ("world_size", self.world_size),
+            ("craber_size", self.craber_size),
+            ("pheromone_cell_size", self.pheromone_cell_size),
+            ("food_spawn_rate", self.food_spawn_rate),
+            ("craber_spawn_rate", self.craber_spawn_rate),
+            ("speciation_interval", self.speciation_interval),
+            ("brain_tick_min_rate", self.brain_tick_min_rate),
+        ] {
+            if !(value.is_finite() && value > 0.0) {
+                return Err(format!("{name} must be positive, got {value}"));
+            }
+        }
+        for (name, value) in [
+            ("mutation_amount", self.mutation_amount),
+            ("gene_mutation_amount", self.gene_mutation_amount),
+            ("gene_transfer_cooldown", self.gene_transfer_cooldown),
+            ("world_energy", self.world_energy),
+            ("energy_consumption_rate", self.energy_consumption_rate),
+            ("linear_drag_coefficient", self.linear_drag_coefficient),
+            ("angular_drag_coefficient", self.angular_drag_coefficient),
+        ] {
+            if !(value.is_finite() && value >= 0.0) {
+                return Err(format!("{name} must not be negative, got {value}"));
+            }
+        }
+        if !(self.brain_tick_max_rate.is_finite()
+            && self.brain_tick_max_rate >= self.brain_tick_min_rate)
+        {
+            return Err(format!(
+                "brain_tick_max_rate ({}) must be at least brain_tick_min_rate ({})",
+                self.brain_tick_max_rate, self.brain_tick_min_rate
+            ));
+        }
+        // Food spawns within `world_size - wall_thickness - food_size` of the centre.
+        let food_bound = self.world_size - self.wall_thickness - self.food_size;
+        if !(food_bound.is_finite() && food_bound > 0.0) {
+            return Err(format!(
+                "world_size ({}) must exceed wall_thickness ({}) plus food_size ({})",
+                self.world_size, self.wall_thickness, self.food_size
+            ));
+        }
+        if self.food_energy_min.is_nan() || self.food_energy_min < 0.0 {
+            return Err(format!("food_energy_min must not be negative, got {}", self.food_energy_min));
+        }
+        if self.food_energy_min >= self.food_energy_max
+            || !self.food_energy_max.is_finite()
+        {
+            return Err(format!(
+                "food_energy_min ({}) must be below food_energy_max ({})",
+                self.food_energy_min, self.food_energy_max
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn to_ron_string(&self) -> Result<String, String> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| format!("failed to serialize config: {e}"))
+    }
+
+    /// Takes over every parameter from `other` except the world layout, which is only read at startup.
+    pub fn apply_live(&mut self, other: &SimConfig) {
+        *self = SimConfig {
+            world_size: self.world_size,
+            wall_thickness: self.wall_thickness,
+            gravity: self.gravity,
+            world_energy: self.world_energy,
+            pheromone_cell_size: self.pheromone_cell_size,
+            ..other.clone()
+        };
+    }
+
+    /// Overrides a single field by name, e.g. `set("max_crabers", "8000")`.
+    /// `value` is parsed as RON and must fit the field's type.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        // `value` is deserialized on its own into an otherwise default config, so it is parsed as
+        // the field's own type and can't reach other fields. The field is then copied over through
+        // its serialized line; `ron::Value` would lose enum variant names.
+        let parsed: SimConfig = ron::from_str(&format!("({name}: {value})"))
+            .map_err(|e| format!("invalid value for {name}: {e}"))?;
+        // The pretty printer puts every field on its own line.
+        let prefix = format!("    {name}: ");
+        let default_text = SimConfig::default().to_ron_string()?;
+        let mut field = None;
+        for (line, default_line) in parsed.to_ron_string()?.lines().zip(default_text.lines()) {
+            if line.starts_with(&prefix) {
+                field = Some(line.to_string());
+            } else if line != default_line {
+                return Err(format!("invalid value for {name}: {value} also sets other fields"));
+            }
+        }
+        let field = field.ok_or_else(|| format!("unknown config field: {name}"))?;
+        let text = self
+            .to_ron_string()?
+            .lines()
+            .map(|line| if line.starts_with(&prefix) { field.as_str() } else { line })
+            .collect::<Vec<_>>()
+            .join("\n");
+        *self = ron::from_str(&text).map_err(|e| format!("invalid value for {name}: {e}"))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_is_valid() {
+        SimConfig::default().validate().unwrap();
+    }
+
+    #[test]
+    fn set_parses_the_value_as_the_fields_own_type() {
+        let mut config = SimConfig::default();
+        config.set("max_crabers", "8000").unwrap();
+        config.set("brain_evaluation", "Topological").unwrap();
+        assert_eq!(config.max_crabers, 8000);
+        assert_eq!(config.brain_evaluation, BrainEvaluation::Topological);
+
+        assert!(config.set("max_crabers", "-1").is_err());
+        assert!(config.set("max_crabers", "10, max_food_count: 0").is_err());
+        assert!(config.set("max_crabers", "10,\n    max_food_count: 0").is_err());
+        assert!(config.set("no_such_field", "1").is_err());
+        assert_eq!(config.max_crabers, 8000);
+        assert_eq!(config.max_food_count, SimConfig::default().max_food_count);
+    }
+}
@@ -1,32 +1,34 @@
 use avian2d::prelude::*;
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
+use bevy::utils::Parallel;
 
 use rand::RngExt;
 use rand::prelude::IndexedRandom;
+use serde::{Deserialize, Serialize};
 
 use crate::common::*;
+use crate::config::SimConfig;
 
 use crate::brain::*;
-
-const ENERGY_CONSUMPTION_RATE: f32 = 0.03;
-const CRABER_HEALING_RATE: f32 = 0.05;
-const CRABER_HEALING_COST: f32 = 1.3;
-const CRABER_DEATH_ENERGY_FACTOR: f32 = 0.7;
-const CRABER_MASS: f32 = 0.5;
-const CRABER_INERTIA: f32 = 0.05;
-pub const CRABER_SIZE: f32 = 10.0;
-pub const CRABER_REQUIRED_REPRODUCE_ENERGY: f32 = 100.0;
-pub const CRABER_REPRODUCE_ENERGY: f32 = 60.0;
-pub const MAX_CRABERS: usize = 5000;
-pub const MAX_CRABERS_SPAWNER: usize = 20;
-pub const CRABER_SPAWN_MULTIPLIER: usize = 1;
-pub const CRABER_MUTATION_CHANCE: f32 = 0.05;
-pub const CRABER_MUTATION_AMOUNT: f32 = 0.5;
+use crate::genetics::*;
+use crate::genome::Genome;
+use crate::headless::HeadlessRun;
+use crate::lineage::*;
+use crate::rng::*;
 
 /// Decay-based input: 1.0 after reproduction, decays toward 0 over time.
 #[derive(Component)]
 pub struct LastReproducedValue(pub f32);
 
+/// Decay-based input: 1.0 after colliding with another craber, decays toward 0 over time.
+#[derive(Component)]
+pub struct RecentlyCollidedValue(pub f32);
+
+/// Decay-based input: 1.0 after being attacked, decays toward 0 over time.
+#[derive(Component)]
+pub struct WasAttackedValue(pub f32);
+
 /// Tracks craber age in seconds since spawn.
 #[derive(Component)]
 pub struct CraberAge(pub f32);
@@ -46,6 +48,15 @@ pub struct RotationAccumulator(pub f32);
 /// Accumulator for brain tick timing. Brain fires when this reaches >= 1.0.
 #[derive(Component)]
 pub struct BrainTickAccumulator(pub f32);
+
+/// Whether the brain ticked during the current fixed step.
+#[derive(Component)]
+pub struct BrainTicked(pub bool);
+
+/// Energy change collected during the current fixed step, applied by `apply_energy_deltas`.
+/// Lets systems that run in parallel charge energy without sending a message per craber.
+#[derive(Component, Default)]
+pub struct EnergyDelta(pub f32);
 pub enum CraberTexture {
     A,
     B,
@@ -78,13 +89,13 @@ pub struct Generation {
     pub generation_id: u32,
 }
 
-#[derive(Component, Copy, Clone, Debug)]
+#[derive(Component, Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Health {
     pub max_health: f32,
     pub health: f32,
 }
 
-#[derive(Component, Copy, Clone, Debug)]
+#[derive(Component, Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Energy {
     pub max_energy: f32,
     pub energy: f32,
@@ -103,6 +114,12 @@ impl Default for ReproduceCooldown {
     }
 }
 
+/// Time until the craber can receive genes again.
+#[derive(Component, Debug)]
+pub struct GeneTransferCooldown {
+    pub timer: Timer,
+}
+
 #[derive(Message)]
 pub struct ReproduceEvent {
     pub entity: Entity,
@@ -123,27 +140,19 @@ pub struct SexualReproduceEvent {
     pub generation: Generation,
 }
 
-pub enum VisionEventType {
-    Craber,
-    Food,
-    Wall,
-}
-
-#[derive(Message)]
-pub struct VisionEvent {
-    pub vision_entity: Entity,
-    pub entity: Entity,
-    pub event_type: VisionEventType,
-    pub entity_id: u8,
-    pub manifolds: Vec<ContactManifold>,
-}
-
 #[derive(Message)]
 pub struct CraberCollisionEvent {
     pub entity_a: Entity,
     pub entity_b: Entity,
 }
 
+/// Horizontal gene transfer from one touching craber to another.
+#[derive(Message)]
+pub struct GeneTransferEvent {
+    pub donor: Entity,
+    pub recipient: Entity,
+}
+
 // Spawn event
 #[derive(Message)]
 pub struct SpawnEvent {
@@ -151,17 +160,16 @@ pub struct SpawnEvent {
     pub roation: Quat,
     pub craber: Craber,
     pub generation: u32,
+    /// Lineage ids of the parents, empty for crabers that weren't born.
+    pub parents: Vec<u64>,
+    /// Phenotype expressed from `genotype`.
     pub new_brain: Brain,
+    pub genome: Genome,
+    pub genotype: Genotype,
     pub health: Health,
     pub energy: Energy,
 }
 
-#[derive(Message)]
-pub struct LoseEnergyEvent {
-    pub entity: Entity,
-    pub energy_lost: f32,
-}
-
 #[derive(Message)]
 pub struct LoseHealthEvent {
     pub entity: Entity,
@@ -178,277 +186,385 @@ pub struct CraberAttackEvent {
 
 pub fn despawn_dead_crabers(
     mut commands: Commands,
-    query: Query<(Entity, &Health), Without<Dying>>,
+    query: Query<(Entity, &Health, &Energy), Without<Dying>>,
     mut craber_despawn_events: MessageWriter<CraberDespawnEvent>,
 ) {
-    for (entity, craber) in query.iter() {
+    for (entity, craber, energy) in query.iter() {
         if craber.health <= 0.0 {
             commands.entity(entity).insert(Dying);
-            craber_despawn_events.write(CraberDespawnEvent { entity });
+            // Health only drops from starving or being attacked
+            let cause = if energy.energy <= 0.0 {
+                CauseOfDeath::Starvation
+            } else {
+                CauseOfDeath::Killed
+            };
+            craber_despawn_events.write(CraberDespawnEvent { entity, cause });
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn craber_despawner(
     mut commands: Commands,
-    query: Query<(Entity, &Health, &Energy, &Transform)>,
+    query: Query<(Entity, &Energy, &Transform, &Lineage)>,
     mut craber_despawn_events: MessageReader<CraberDespawnEvent>,
     mut food_spawn_events: MessageWriter<FoodSpawnEvent>,
     mut stats: ResMut<SimulationStats>,
+    mut lineage_tree: ResMut<LineageTree>,
+    mut energy_pool: ResMut<EnergyPool>,
+    time: Res<Time>,
+    config: Res<SimConfig>,
 ) {
     for event in craber_despawn_events.read() {
-        if let Ok((craber_entity, _craber_health, craber_energy, craber_transform)) =
+        if let Ok((craber_entity, craber_energy, craber_transform, lineage)) =
             query.get(event.entity)
         {
             commands.entity(craber_entity).despawn();
             stats.death_counter += 1;
-            let new_food_energy = craber_energy.energy * CRABER_DEATH_ENERGY_FACTOR;
-            food_spawn_events.write(FoodSpawnEvent {
-                transform: craber_transform.clone(),
-                food_energy: new_food_energy,
-            });
+            lineage_tree.record_death(lineage.0, time.elapsed_secs_f64(), event.cause);
+            // The part of the body that isn't left behind as food returns to the pool
+            let held = craber_energy.energy.max(0.0);
+            let new_food_energy = (held * config.death_energy_factor).clamp(0.0, held);
+            energy_pool.deposit(held as f64 - new_food_energy as f64);
+            if new_food_energy > 0.0 {
+                food_spawn_events.write(FoodSpawnEvent {
+                    transform: craber_transform.clone(),
+                    food_energy: new_food_energy,
+                });
+            }
         }
     }
 }
 
+/// Rendering resources for craber sprites and vision meshes.
+/// Crabers are spawned without visuals in headless runs.
+#[derive(SystemParam)]
+pub struct CraberVisuals<'w> {
+    headless: Option<Res<'w, HeadlessRun>>,
+    asset_server: Option<Res<'w, AssetServer>>,
+    meshes: Option<ResMut<'w, Assets<Mesh>>>,
+    materials: Option<ResMut<'w, Assets<ColorMaterial>>>,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn spawn_craber(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    mut visuals: CraberVisuals,
+    mut sim_rng: ResMut<SimRng>,
+    config: Res<SimConfig>,
     mut spawn_events: MessageReader<SpawnEvent>,
+    mut lineage_tree: ResMut<LineageTree>,
+    mut energy_pool: ResMut<EnergyPool>,
+    time: Res<Time>,
     crabers_query: Query<&Craber>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
 ) {
-    if crabers_query.iter().len() >= MAX_CRABERS {
-        return;
-    }
-    let mut rng = rand::rng();
+    let full = crabers_query.iter().len() >= config.max_crabers;
     for event in spawn_events.read() {
-        let craber = event.craber;
-        let position = event.position;
-        let generation = event.generation;
-        let health = event.health;
-        let energy = event.energy;
-        let rotation = event.roation;
-        let craber_texture = [
-            CraberTexture::A,
-            CraberTexture::B,
-            CraberTexture::C,
-            CraberTexture::D,
-            CraberTexture::E,
-        ]
-        .choose(&mut rng)
-        .unwrap();
-
-        let new_craber = commands
-            .spawn(RigidBody::Dynamic)
-            .insert(Collider::circle(CRABER_SIZE / 2.0))
-            .insert(ColliderDensity(2.5))
-            .insert(Mass(CRABER_MASS))
-            .insert(AngularInertia(CRABER_INERTIA))
-            .insert(Restitution::new(0.8))
-            .insert(AngularDamping(0.0))
-            .insert(LinearDamping(0.0))
-            .insert(KickAccumulator(0.0))
-            .insert(RotationAccumulator(0.0))
-            .insert(BrainTickAccumulator(0.0))
-            .insert(Name::new("Craber"))
-            .insert((
-                Sprite {
-                    image: asset_server.load(craber_texture.path()),
-                    color: Color::srgb(1.0, 1.0, 1.0),
-                    custom_size: Some(Vec2::new(CRABER_SIZE, CRABER_SIZE)),
-                    ..default()
-                },
-                Transform {
-                    translation: position,
-                    rotation,
-                    ..default()
-                },
-            ))
-            .insert(craber)
-            .insert(health)
-            .insert(energy)
-            .insert(SelectableEntity::Craber)
-            .insert(Weight { weight: 1.0 })
-            .insert(Generation {
-                generation_id: generation,
-            })
-            .insert(CollisionLayers::new(
-                [Layer::Craber],
-                [Layer::Food, Layer::Craber, Layer::Wall, Layer::Vision],
-            ))
-            .insert(Friction::new(0.8))
-            .insert(event.new_brain.clone())
-            .insert(EntityType::Craber)
-            .insert(ReproduceCooldown::default())
-            .insert(LastReproducedValue(0.0))
-            .insert(CraberAge(0.0))
-            .insert(ChildrenCount(0))
-            .id();
-        let vision = Vision {
-            radius: 100.0,
-            nearest_food_direction: 0.0,
-            nearest_food_distance: 0.0,
-            nearest_craber_direction: 0.0,
-            nearest_craber_distance: 0.0,
-            nearest_craber_genetic_closeness: 0.0,
-            nearest_wall_direction: 0.0,
-            nearest_wall_distance: 0.0,
-            see_food: false,
-            see_craber: false,
-            see_wall: false,
-            entities_in_vision: Vec::new(),
-            food_seen_timer: 0.0,
-            craber_seen_timer: 0.0,
-            wall_seen_timer: 0.0,
-        };
-        let rand_pretty_color = Color::srgba(
-            rand::rng().random_range(0.0..1.0),
-            rand::rng().random_range(0.0..1.0),
-            rand::rng().random_range(0.0..1.0),
-            0.2,
+        // Crabers that don't fit hand the energy they were given back to the pool
+        if full {
+            energy_pool.deposit(event.energy.energy as f64);
+            continue;
+        }
+        let entity = spawn_craber_entity(&mut commands, &mut visuals, &mut sim_rng, &config, event);
+        let id = lineage_tree.record_birth(
+            &event.parents,
+            event.generation,
+            time.elapsed_secs_f64(),
+            entity,
         );
-        let craber_vision = commands
-            .spawn((Collider::circle(vision.radius), Sensor))
-            .insert(Name::new("CraberVision"))
-            .insert((
-                Mesh2d(meshes.add(Circle::new(vision.radius))),
-                MeshMaterial2d(materials.add(rand_pretty_color)),
-                Transform {
-                    translation: Vec3::new(0., 0., 0.1),
-                    ..default()
-                },
-            ))
-            .insert(CollisionLayers::new(
-                [Layer::Vision],
-                [Layer::Food, Layer::Craber, Layer::Wall],
-            ))
-            .insert(vision)
-            .insert(Weight { weight: 0.0 })
-            .insert(EntityType::Vision)
-            .id();
-
-        commands.entity(new_craber).add_child(craber_vision);
+        commands.entity(entity).insert(Lineage(id));
+    }
+}
+
+/// Spawns a craber (and its vision child) described by `event`, returning the craber entity.
+/// Shared by `spawn_craber` and world snapshot loading.
+pub fn spawn_craber_entity(
+    commands: &mut Commands,
+    visuals: &mut CraberVisuals,
+    sim_rng: &mut SimRng,
+    config: &SimConfig,
+    event: &SpawnEvent,
+) -> Entity {
+    // Appearance is drawn even without visuals, so headless runs consume the same randomness
+    let mut rng = sim_rng.fork(RngStream::Crabers);
+    let craber = event.craber;
+    let position = event.position;
+    let generation = event.generation;
+    let health = event.health;
+    let energy = event.energy;
+    let rotation = event.roation;
+    let genome = event.genome;
+    let craber_texture = [
+        CraberTexture::A,
+        CraberTexture::B,
+        CraberTexture::C,
+        CraberTexture::D,
+        CraberTexture::E,
+    ]
+    .choose(&mut rng)
+    .unwrap();
+
+    let new_craber = commands
+        .spawn(RigidBody::Dynamic)
+        .insert(Collider::circle(genome.size / 2.0))
+        .insert(ColliderDensity(2.5))
+        .insert(Mass(genome.mass(config)))
+        .insert(AngularInertia(genome.inertia(config)))
+        .insert(Restitution::new(0.8))
+        .insert(AngularDamping(0.0))
+        .insert(LinearDamping(0.0))
+        .insert(KickAccumulator(0.0))
+        .insert(RotationAccumulator(0.0))
+        .insert(BrainTickAccumulator(0.0))
+        .insert(BrainTicked(false))
+        .insert(EnergyDelta::default())
+        .insert(Name::new("Craber"))
+        .insert(Transform {
+            translation: position,
+            rotation,
+            ..default()
+        })
+        .insert(craber)
+        .insert(health)
+        .insert(energy)
+        .insert(SelectableEntity::Craber)
+        .insert(Weight { weight: 1.0 })
+        .insert(Generation {
+            generation_id: generation,
+        })
+        .insert(CollisionLayers::new(
+            [Layer::Craber],
+            [Layer::Food, Layer::Craber, Layer::Wall],
+        ))
+        .insert(Friction::new(0.8))
+        .insert(event.new_brain.compile())
+        .insert(event.new_brain.clone())
+        .insert(genome)
+        .insert(event.genotype.clone())
+        .insert(EntityType::Craber)
+        .insert(ReproduceCooldown::default())
+        .insert(GeneTransferCooldown {
+            timer: Timer::from_seconds(config.gene_transfer_cooldown, TimerMode::Once),
+        })
+        .insert(LastReproducedValue(0.0))
+        .insert((RecentlyCollidedValue(0.0), WasAttackedValue(0.0)))
+        .insert(CraberAge(0.0))
+        .insert(ChildrenCount(0))
+        .id();
+    let show_visuals = visuals.headless.is_none();
+    if let Some(asset_server) = visuals.asset_server.as_ref().filter(|_| show_visuals) {
+        commands.entity(new_craber).insert(Sprite {
+            image: asset_server.load(craber_texture.path()),
+            color: Color::srgb(1.0, 1.0, 1.0),
+            custom_size: Some(Vec2::splat(genome.size)),
+            ..default()
+        });
+    }
+    let vision = Vision {
+        radius: genome.vision_radius,
+        nearest_food_direction: 0.0,
+        nearest_food_distance: 0.0,
+        nearest_craber_direction: 0.0,
+        nearest_craber_distance: 0.0,
+        nearest_craber_genetic_closeness: 0.0,
+        nearest_wall_direction: 0.0,
+        nearest_wall_distance: 0.0,
+        see_food: false,
+        see_craber: false,
+        see_wall: false,
+        visible_food: Vec::new(),
+        visible_crabers: Vec::new(),
+        eye_hits: [None; MAX_EYES as usize],
+        food_seen_timer: 0.0,
+        craber_seen_timer: 0.0,
+        wall_seen_timer: 0.0,
+    };
+    let [red, green, blue] = genome.color;
+    let vision_color = Color::srgba(red, green, blue, 0.2);
+    let vision_radius = vision.radius;
+    // Vision is sensed through the `SpatialIndex`, the child only carries its state and visuals
+    let craber_vision = commands
+        .spawn(Name::new("CraberVision"))
+        .insert(Transform {
+            translation: Vec3::new(0., 0., 0.1),
+            ..default()
+        })
+        .insert(vision)
+        .insert(EntityType::Vision)
+        .id();
+    if let (true, Some(meshes), Some(materials)) =
+        (show_visuals, &mut visuals.meshes, &mut visuals.materials)
+    {
+        commands.entity(craber_vision).insert((
+            Mesh2d(meshes.add(Circle::new(vision_radius))),
+            MeshMaterial2d(materials.add(vision_color)),
+        ));
     }
+
+    commands
+        .entity(new_craber)
+        .insert(CraberRng(rng))
+        .add_child(craber_vision);
+    new_craber
 }
 
 pub fn craber_spawner(
     time: Res<Time>,
     mut timer: ResMut<CraberSpawnTimer>,
+    mut sim_rng: ResMut<SimRng>,
+    config: Res<SimConfig>,
     mut spawn_events: MessageWriter<SpawnEvent>,
+    mut energy_pool: ResMut<EnergyPool>,
     crabers_query: Query<&Craber>,
 ) {
     if timer.0.tick(time.delta()).just_finished() {
-        for _ in 0..CRABER_SPAWN_MULTIPLIER {
-            if crabers_query.iter().len() >= MAX_CRABERS_SPAWNER {
+        for _ in 0..config.craber_spawn_multiplier {
+            if crabers_query.iter().len() >= config.max_crabers_spawner {
                 continue;
             }
-            let mut rng = rand::rng();
+            if !energy_pool.withdraw(100.0) {
+                break;
+            }
+            let rng = sim_rng.stream(RngStream::CraberSpawner);
             let position = Vec3::new(
-                rng.random_range((WORLD_SIZE * -1.)..WORLD_SIZE),
-                rng.random_range((WORLD_SIZE * -1.)..WORLD_SIZE),
+                rng.random_range((config.world_size * -1.)..config.world_size),
+                rng.random_range((config.world_size * -1.)..config.world_size),
                 0.0,
             );
             let rotation = Quat::from_rotation_z(rng.random_range(0.0..std::f32::consts::PI * 2.0));
+            let genotype =
+                Genotype::homozygous(Brain::default(), Genome::new(&config, Genome::random_color(rng)));
+            let (new_brain, genome) = genotype.express();
             spawn_events.write(SpawnEvent {
                 position,
                 roation: rotation,
                 generation: 0,
+                parents: Vec::new(),
                 craber: Craber {},
                 health: Health {
-                    max_health: 100.,
-                    health: 100.,
+                    max_health: genome.max_health,
+                    health: genome.max_health,
                 },
                 energy: Energy {
-                    max_energy: 100.,
+                    max_energy: genome.max_energy,
                     energy: 100.,
                 },
-                new_brain: Brain::default(),
+                new_brain,
+                genome,
+                genotype,
             });
         }
     }
 }
 
+type EnergyConsumptionQueryData<'a> = (
+    Entity,
+    &'a mut Health,
+    &'a Energy,
+    &'a mut LinearVelocity,
+    &'a Generation,
+    &'a Brain,
+    &'a mut ReproduceCooldown,
+    &'a mut GeneTransferCooldown,
+    &'a mut EnergyDelta,
+    &'a Genome,
+);
+
 // Make crabers lose energy over time
+#[allow(clippy::too_many_arguments)]
 pub fn energy_consumption(
-    mut query: Query<(
-        Entity,
-        &mut Health,
-        &mut Energy,
-        &mut LinearVelocity,
-        &Generation,
-        &Brain,
-        &mut ReproduceCooldown,
-    )>,
+    mut query: Query<EnergyConsumptionQueryData>,
     time: Res<Time>,
+    config: Res<SimConfig>,
     mut reproduce_events: MessageWriter<ReproduceEvent>,
     mut sexual_request_events: MessageWriter<SexualReproduceRequestEvent>,
+    mut reproduce_queue: Local<Parallel<Vec<ReproduceEvent>>>,
+    mut sexual_request_queue: Local<Parallel<Vec<SexualReproduceRequestEvent>>>,
 ) {
-    for (entity, mut health, mut energy, _velocity, generation, brain, mut cooldown) in
-        query.iter_mut()
-    {
-        let delta_seconds = time.delta_secs();
-        energy.energy -= ENERGY_CONSUMPTION_RATE * delta_seconds;
-        if health.health < 100.0 {
-            health.health += CRABER_HEALING_RATE * delta_seconds;
-            energy.energy -= CRABER_HEALING_COST * delta_seconds;
-        }
-        // Tick the reproduction cooldown
-        cooldown.timer.tick(time.delta());
-        if energy.energy >= CRABER_REQUIRED_REPRODUCE_ENERGY && cooldown.timer.is_finished() {
-            // Neural-network gated reproduction: craber must want to reproduce
-            if brain.get_want_to_reproduce() < 1.0 {
-                // Not ready to reproduce yet
-            } else {
-                let new_generation = Generation {
-                    generation_id: generation.generation_id + 1,
-                };
-                let want_sex = brain.get_want_sexual_reproduction();
-                if want_sex >= 0.5 {
-                    sexual_request_events.write(SexualReproduceRequestEvent {
-                        bearer: entity,
-                        generation: new_generation,
-                        want_sex,
-                    });
+    let delta_seconds = time.delta_secs();
+    query.par_iter_mut().for_each(
+        |(
+            entity,
+            mut health,
+            energy,
+            _velocity,
+            generation,
+            brain,
+            mut cooldown,
+            mut gene_transfer_cooldown,
+            mut energy_delta,
+            genome,
+        )| {
+            energy_delta.0 -= config.energy_consumption_rate * delta_seconds;
+            energy_delta.0 -= config.body_upkeep_cost * genome.upkeep(&config) * delta_seconds;
+            if health.health < health.max_health {
+                health.health += config.healing_rate * delta_seconds;
+                energy_delta.0 -= config.healing_cost * delta_seconds;
+            }
+            // Tick the reproduction cooldown
+            cooldown.timer.tick(time.delta());
+            gene_transfer_cooldown.timer.tick(time.delta());
+            // Small tanks can't hold the full requirement, so a full tank is enough
+            let required_energy = config.required_reproduce_energy.min(energy.max_energy);
+            if energy.energy >= required_energy && cooldown.timer.is_finished() {
+                // Neural-network gated reproduction: craber must want to reproduce
+                if brain.get_want_to_reproduce() < 1.0 {
+                    // Not ready to reproduce yet
                 } else {
-                    reproduce_events.write(ReproduceEvent {
-                        entity,
-                        generation: new_generation,
-                    });
+                    let new_generation = Generation {
+                        generation_id: generation.generation_id + 1,
+                    };
+                    let want_sex = brain.get_want_sexual_reproduction();
+                    if want_sex >= 0.5 {
+                        sexual_request_queue.borrow_local_mut().push(SexualReproduceRequestEvent {
+                            bearer: entity,
+                            generation: new_generation,
+                            want_sex,
+                        });
+                    } else {
+                        reproduce_queue.borrow_local_mut().push(ReproduceEvent {
+                            entity,
+                            generation: new_generation,
+                        });
+                    }
+                    cooldown.timer.reset();
                 }
-                cooldown.timer.reset();
             }
-        }
-        // Handle low energy situations
-        if energy.energy <= 0.0 {
-            health.health -= 60.0 * delta_seconds;
-        }
-    }
+            // Handle low energy situations
+            if energy.energy <= 0.0 {
+                health.health -= 60.0 * delta_seconds;
+            }
+        },
+    );
+
+    // Thread-local queues drain in scheduling order, so sort to keep seeded runs reproducible
+    let mut reproduce: Vec<ReproduceEvent> = reproduce_queue.drain().collect();
+    reproduce.sort_by_key(|event| event.entity);
+    reproduce_events.write_batch(reproduce);
+    let mut sexual_requests: Vec<SexualReproduceRequestEvent> = sexual_request_queue.drain().collect();
+    sexual_requests.sort_by_key(|event| event.bearer);
+    sexual_request_events.write_batch(sexual_requests);
 }
 
 pub fn match_sexual_partners(
     mut sexual_request_events: MessageReader<SexualReproduceRequestEvent>,
-    craber_query: Query<(&Children, &Brain)>,
+    mut craber_query: Query<(&Children, &Brain, &mut CraberRng)>,
     vision_query: Query<&Vision>,
     brain_query: Query<&Brain>,
     mut sexual_reproduce_events: MessageWriter<SexualReproduceEvent>,
     mut reproduce_events: MessageWriter<ReproduceEvent>,
 ) {
     for event in sexual_request_events.read() {
-        let Ok((children, _bearer_brain)) = craber_query.get(event.bearer) else {
+        let Ok((children, _bearer_brain, mut bearer_rng)) = craber_query.get_mut(event.bearer) else {
             continue;
         };
-        // Find the vision child to get entities in range
+        // Find the vision child to get the crabers in range
         let mut found_mate = false;
         for child in children.iter() {
             let Ok(vision) = vision_query.get(child) else {
                 continue;
             };
-            for &visible_entity in &vision.entities_in_vision {
-                if visible_entity == event.bearer {
-                    continue;
-                }
+            for &visible_entity in &vision.visible_crabers {
                 if let Ok(mate_brain) = brain_query.get(visible_entity) {
                     if mate_brain.get_want_sexual_reproduction() >= 0.5 {
                         sexual_reproduce_events.write(SexualReproduceEvent {
@@ -470,7 +586,7 @@ pub fn match_sexual_partners(
         // Probabilistic fallback: the higher want_sex, the less likely we fall back
         if !found_mate {
             let fallback_chance = ((1.0 - event.want_sex) / 0.5).clamp(0.0, 1.0);
-            if rand::rng().random_range(0.0..1.0) < fallback_chance {
+            if bearer_rng.0.random_range(0.0..1.0) < fallback_chance {
                 reproduce_events.write(ReproduceEvent {
                     entity: event.bearer,
                     generation: Generation {
@@ -482,57 +598,76 @@ pub fn match_sexual_partners(
     }
 }
 
+type ReproduceQueryData<'a> = (
+    &'a Transform,
+    &'a Genotype,
+    &'a Genome,
+    &'a mut Energy,
+    &'a mut LastReproducedValue,
+    &'a mut ChildrenCount,
+    &'a mut CraberRng,
+    &'a Lineage,
+);
+
 pub fn craber_sexual_reproduce(
-    mut craber_query: Query<(&Transform, &Brain, &mut Energy, &mut LastReproducedValue, &mut ChildrenCount)>,
+    mut craber_query: Query<ReproduceQueryData>,
     mut sexual_reproduce_events: MessageReader<SexualReproduceEvent>,
     mut spawn_events: MessageWriter<SpawnEvent>,
     mut stats: ResMut<SimulationStats>,
+    mut innovations: ResMut<InnovationRegistry>,
+    config: Res<SimConfig>,
 ) {
     for event in sexual_reproduce_events.read() {
-        // Get mate brain first (immutable borrow)
-        let mate_brain = if let Ok((_, brain, _, _, _)) = craber_query.get(event.mate) {
-            brain.clone()
-        } else {
-            continue;
-        };
+        // Get mate genotype first (immutable borrow)
+        let (mate_genotype, mate_lineage) =
+            if let Ok((_, genotype, _, _, _, _, _, lineage)) = craber_query.get(event.mate) {
+                (genotype.clone(), *lineage)
+            } else {
+                continue;
+            };
 
         // Now get bearer (mutable borrow)
-        let Ok((transform, brain, mut energy, mut last_reproduced, mut children_count)) = craber_query.get_mut(event.bearer) else {
+        let Ok((transform, genotype, genome, mut energy, mut last_reproduced, mut children_count, mut rng, lineage)) =
+            craber_query.get_mut(event.bearer)
+        else {
             continue;
         };
-        if energy.energy < CRABER_REPRODUCE_ENERGY {
+        if energy.energy < config.reproduce_energy {
             continue;
         }
-        energy.energy -= CRABER_REPRODUCE_ENERGY;
+        energy.energy -= config.reproduce_energy;
         last_reproduced.0 = 1.0;
         children_count.0 += 1;
 
-        let child_brain = brain.crossover_brain(
-            &mate_brain,
-            CRABER_MUTATION_CHANCE,
-            CRABER_MUTATION_AMOUNT,
-            CRABER_MUTATION_CHANCE,
-        );
+        // One chromosome set from each parent
+        let child_genotype = Genotype([
+            genotype.gamete(&config, &mut innovations, &mut rng.0),
+            mate_genotype.gamete(&config, &mut innovations, &mut rng.0),
+        ]);
+        let (child_brain, child_genome) = child_genotype.express();
 
         // Spawn offspring between the two parents
         let parent_angle = transform.rotation.to_axis_angle().1;
-        let position_offset = Vec2::new(parent_angle.cos(), parent_angle.sin()) * CRABER_SIZE * 5.0;
+        let position_offset = Vec2::new(parent_angle.cos(), parent_angle.sin()) * genome.size * 5.0;
         let position = transform.translation + position_offset.extend(0.0);
         let rotation = Quat::from_rotation_z(parent_angle + std::f32::consts::PI);
 
         spawn_events.write(SpawnEvent {
             position,
             new_brain: child_brain,
+            genome: child_genome,
+            genotype: child_genotype,
             generation: event.generation.generation_id,
+            parents: vec![lineage.0, mate_lineage.0],
             roation: rotation,
             craber: Craber {},
             health: Health {
-                max_health: 100.0,
-                health: 50.0,
+                max_health: child_genome.max_health,
+                health: child_genome.max_health * 0.5,
             },
             energy: Energy {
-                max_energy: 100.0,
-                energy: CRABER_REPRODUCE_ENERGY,
+                max_energy: child_genome.max_energy,
+                energy: config.reproduce_energy,
             },
         });
         stats.birth_counter += 1;
@@ -542,48 +677,52 @@ pub fn craber_sexual_reproduce(
 
 // TODO: Make reproduction for plants/food? Would need a separate health/energy component
 pub fn craber_reproduce(
-    mut craber_query: Query<(&Transform, &Brain, &mut Energy, &mut LastReproducedValue, &mut ChildrenCount)>,
+    mut craber_query: Query<ReproduceQueryData>,
     mut reproduce_events: MessageReader<ReproduceEvent>,
     mut spawn_events: MessageWriter<SpawnEvent>,
     mut stats: ResMut<SimulationStats>,
+    mut innovations: ResMut<InnovationRegistry>,
+    config: Res<SimConfig>,
 ) {
     for event in reproduce_events.read() {
-        if let Ok((transform, brain, mut energy, mut last_reproduced, mut children_count)) = craber_query.get_mut(event.entity) {
+        if let Ok((transform, genotype, genome, mut energy, mut last_reproduced, mut children_count, mut rng, lineage)) =
+            craber_query.get_mut(event.entity)
+        {
             // Guard: ensure parent still has enough energy (may have been spent since event was sent)
-            if energy.energy < CRABER_REPRODUCE_ENERGY {
+            if energy.energy < config.reproduce_energy {
                 continue;
             }
             // Deduct energy directly to prevent multi-frame burst
-            energy.energy -= CRABER_REPRODUCE_ENERGY;
+            energy.energy -= config.reproduce_energy;
             last_reproduced.0 = 1.0;
             children_count.0 += 1;
 
             // Position offset from parent to the back, first find the angle of the parent
             let parent_angle = transform.rotation.to_axis_angle().1;
             let position_offset =
-                Vec2::new(parent_angle.cos(), parent_angle.sin()) * CRABER_SIZE * 5.0;
+                Vec2::new(parent_angle.cos(), parent_angle.sin()) * genome.size * 5.0;
             let position = transform.translation + position_offset.extend(0.0);
 
             // Rotation 180 degrees from parent
             let rotation = Quat::from_rotation_z(parent_angle + std::f32::consts::PI);
+            let child_genotype = genotype.mutated(&config, &mut innovations, &mut rng.0);
+            let (child_brain, child_genome) = child_genotype.express();
             spawn_events.write(SpawnEvent {
                 position,
-                new_brain: brain.new_mutated_brain(
-                    CRABER_MUTATION_CHANCE,
-                    CRABER_MUTATION_AMOUNT,
-                    CRABER_MUTATION_CHANCE,
-                    CRABER_MUTATION_CHANCE,
-                ),
+                new_brain: child_brain,
+                genome: child_genome,
+                genotype: child_genotype,
                 generation: event.generation.generation_id,
+                parents: vec![lineage.0],
                 roation: rotation,
                 craber: Craber {},
                 health: Health {
-                    max_health: 100.0,
-                    health: 50.0,
+                    max_health: child_genome.max_health,
+                    health: child_genome.max_health * 0.5,
                 },
                 energy: Energy {
-                    max_energy: 100.0,
-                    energy: CRABER_REPRODUCE_ENERGY,
+                    max_energy: child_genome.max_energy,
+                    energy: config.reproduce_energy,
                 },
             });
             stats.birth_counter += 1;
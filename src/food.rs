@@ -5,8 +5,9 @@ use avian2d::prelude::*;
 use rand::RngExt;
 
 use crate::common::*;
-
-pub const FOOD_SIZE: f32 = 10.0;
+use crate::config::SimConfig;
+use crate::headless::HeadlessRun;
+use crate::rng::*;
 
 #[derive(Component)]
 pub struct Food {
@@ -16,21 +17,28 @@ pub struct Food {
 pub fn food_spawner(
     time: Res<Time>,
     mut timer: ResMut<FoodSpawnTimer>,
+    mut sim_rng: ResMut<SimRng>,
+    config: Res<SimConfig>,
     mut food_spawn_event: MessageWriter<FoodSpawnEvent>,
+    mut energy_pool: ResMut<EnergyPool>,
     food_query: Query<&Food>,
 ) {
     let times = timer.0.tick(time.delta()).times_finished_this_tick();
     for _ in 0..times {
-        if food_query.iter().count() >= crate::MAX_FOOD_COUNT {
+        if food_query.iter().count() >= config.max_food_count {
             return;
         }
-        let mut rng = rand::rng();
-        let bound = WORLD_SIZE - WALL_THICKNESS - FOOD_SIZE;
+        let rng = sim_rng.stream(RngStream::Food);
+        let bound = config.world_size - config.wall_thickness - config.food_size;
         let position = Vec2::new(
             rng.random_range(-bound..bound),
             rng.random_range(-bound..bound),
         );
-        let energy_value = rng.random_range(5.0..15.0);
+        let energy_value = rng.random_range(config.food_energy_min..config.food_energy_max);
+        // Food only grows from energy the world has to spare
+        if !energy_pool.withdraw(energy_value as f64) {
+            return;
+        }
         food_spawn_event.write(FoodSpawnEvent {
             transform: Transform::from_translation(position.extend(0.0)),
             food_energy: energy_value,
@@ -41,21 +49,22 @@ pub fn food_spawner(
 pub fn spawn_food(
     mut commands: Commands<'_, '_>,
     mut food_spawn_event: MessageReader<FoodSpawnEvent>,
+    headless: Option<Res<HeadlessRun>>,
+    config: Res<SimConfig>,
 ) {
     for event in food_spawn_event.read() {
         if event.food_energy < 0. {
             continue;
         }
-        commands
-            .spawn((
-                Sprite {
-                    color: Color::from(BLUE),
-                    custom_size: Some(Vec2::new(FOOD_SIZE, FOOD_SIZE)),
-                    ..default()
-                },
-                event.transform,
-            ))
-            .insert(Collider::circle(FOOD_SIZE / 2.0))
+        let mut food = commands.spawn(event.transform);
+        if headless.is_none() {
+            food.insert(Sprite {
+                color: Color::from(BLUE),
+                custom_size: Some(Vec2::new(config.food_size, config.food_size)),
+                ..default()
+            });
+        }
+        food.insert(Collider::circle(config.food_size / 2.0))
             .insert(Food {
                 energy_value: event.food_energy,
             })
@@ -63,7 +72,7 @@ pub fn spawn_food(
             .insert(EntityType::Food)
             .insert(CollisionLayers::new(
                 [Layer::Food],
-                [Layer::Food, Layer::Craber, Layer::Vision],
+                [Layer::Food, Layer::Craber],
             ))
             .insert(Weight { weight: 1.0 });
     }
@@ -0,0 +1,124 @@
+use bevy::prelude::*;
+use rand::{Rng, RngExt};
+use serde::{Deserialize, Serialize};
+
+use crate::brain::*;
+use crate::config::SimConfig;
+use crate::genome::*;
+
+/// One chromosome set: a haploid brain and the body alleles with their dominance.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Haplotype {
+    pub brain: Brain,
+    pub body: Genome,
+    pub body_dominance: BodyDominance,
+}
+
+impl Haplotype {
+    fn mutated<R: Rng + ?Sized>(
+        &self,
+        config: &SimConfig,
+        innovations: &mut InnovationRegistry,
+        rng: &mut R,
+    ) -> Haplotype {
+        let brain = self.brain.new_mutated_brain(
+            config.mutation_chance,
+            config.mutation_amount,
+            config.insertion_chance,
+            config.deletion_chance,
+            innovations,
+            rng,
+        );
+        let body = self.body.mutated(config.gene_mutation_chance, config.gene_mutation_amount, rng);
+        let mut body_dominance = self.body_dominance;
+        body_dominance.mutate(config.gene_mutation_chance, config.gene_mutation_amount, rng);
+        Haplotype {
+            brain,
+            body,
+            body_dominance,
+        }
+    }
+}
+
+/// Both chromosome sets of a craber. Its `Brain` and `Genome` components are the phenotype
+/// expressed from them.
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
+pub struct Genotype(pub [Haplotype; 2]);
+
+impl Genotype {
+    /// Two identical chromosome sets, for crabers that weren't born.
+    pub fn homozygous(brain: Brain, body: Genome) -> Self {
+        let haplotype = Haplotype {
+            brain,
+            body,
+            body_dominance: BodyDominance::default(),
+        };
+        Self([haplotype.clone(), haplotype])
+    }
+
+    pub fn express(&self) -> (Brain, Genome) {
+        let [a, b] = &self.0;
+        (
+            a.brain.express(&b.brain),
+            a.body.express(&a.body_dominance, &b.body, &b.body_dominance),
+        )
+    }
+
+    /// Meiosis: recombines both chromosome sets into a single mutated one to pass on.
+    pub fn gamete<R: Rng + ?Sized>(
+        &self,
+        config: &SimConfig,
+        innovations: &mut InnovationRegistry,
+        rng: &mut R,
+    ) -> Haplotype {
+        // Crossover keeps every gene only the first parent carries, so alternate which goes first
+        let [a, b] = if rng.random_bool(0.5) {
+            [&self.0[0], &self.0[1]]
+        } else {
+            [&self.0[1], &self.0[0]]
+        };
+        let brain = a.brain.crossover_brain(
+            &b.brain,
+            config.mutation_chance,
+            config.mutation_amount,
+            config.insertion_chance,
+            config.deletion_chance,
+            innovations,
+            rng,
+        );
+        let (mut body, mut body_dominance) =
+            a.body.recombine(&a.body_dominance, &b.body, &b.body_dominance, rng);
+        body.mutate(config.gene_mutation_chance, config.gene_mutation_amount, rng);
+        body_dominance.mutate(config.gene_mutation_chance, config.gene_mutation_amount, rng);
+        Haplotype {
+            brain,
+            body,
+            body_dominance,
+        }
+    }
+
+    /// Asexual offspring keep both chromosome sets, each mutated on its own.
+    pub fn mutated<R: Rng + ?Sized>(
+        &self,
+        config: &SimConfig,
+        innovations: &mut InnovationRegistry,
+        rng: &mut R,
+    ) -> Genotype {
+        Genotype([
+            self.0[0].mutated(config, innovations, rng),
+            self.0[1].mutated(config, innovations, rng),
+        ])
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, haplotype) in self.0.iter().enumerate() {
+            haplotype
+                .brain
+                .validate()
+                .and_then(|_| haplotype.body.validate())
+                .and_then(|_| haplotype.body_dominance.validate())
+                .map_err(|e| format!("chromosome set {i}: {e}"))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,246 @@
+use bevy::prelude::*;
+use rand::{Rng, RngExt};
+use serde::{Deserialize, Serialize};
+
+use crate::config::SimConfig;
+
+/// Health, energy capacity and vision radius of the default body.
+pub const BASE_MAX_HEALTH: f32 = 100.0;
+pub const BASE_MAX_ENERGY: f32 = 100.0;
+pub const BASE_VISION_RADIUS: f32 = 100.0;
+
+const SIZE_RANGE: (f32, f32) = (4.0, 40.0);
+const MAX_HEALTH_RANGE: (f32, f32) = (20.0, 500.0);
+const MAX_ENERGY_RANGE: (f32, f32) = (20.0, 500.0);
+const POWER_RANGE: (f32, f32) = (0.1, 5.0);
+const VISION_RADIUS_RANGE: (f32, f32) = (20.0, 400.0);
+
+/// Share of the upkeep each gene accounts for in the default body. Sums to 1.
+const SIZE_UPKEEP: f32 = 0.3;
+const MAX_HEALTH_UPKEEP: f32 = 0.15;
+const MAX_ENERGY_UPKEEP: f32 = 0.15;
+const POWER_UPKEEP: f32 = 0.25;
+const VISION_UPKEEP: f32 = 0.15;
+
+/// Body genes, inherited and mutated separately from the brain.
+#[derive(Component, Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Genome {
+    /// Body diameter. Mass grows with the area, inertia with the area times the radius squared.
+    pub size: f32,
+    pub max_health: f32,
+    pub max_energy: f32,
+    /// Scales kick impulses and attack damage.
+    pub power: f32,
+    /// sRGB tint of the vision disc.
+    pub color: [f32; 3],
+    pub vision_radius: f32,
+}
+
+impl Genome {
+    /// The body every craber had before genes existed, in the given colour.
+    pub fn new(config: &SimConfig, color: [f32; 3]) -> Self {
+        Self {
+            size: config.craber_size,
+            max_health: BASE_MAX_HEALTH,
+            max_energy: BASE_MAX_ENERGY,
+            power: 1.0,
+            color,
+            vision_radius: BASE_VISION_RADIUS,
+        }
+    }
+
+    pub fn random_color<R: Rng + ?Sized>(rng: &mut R) -> [f32; 3] {
+        [
+            rng.random_range(0.0..1.0),
+            rng.random_range(0.0..1.0),
+            rng.random_range(0.0..1.0),
+        ]
+    }
+
+    /// Size relative to `craber_size`.
+    pub fn scale(&self, config: &SimConfig) -> f32 {
+        self.size / config.craber_size
+    }
+
+    pub fn mass(&self, config: &SimConfig) -> f32 {
+        config.craber_mass * self.scale(config).powi(2)
+    }
+
+    pub fn inertia(&self, config: &SimConfig) -> f32 {
+        config.craber_inertia * self.scale(config).powi(4)
+    }
+
+    /// Upkeep relative to the default body, which costs 1.0. Each gene costs in proportion to its
+    /// value, size with the body's area.
+    pub fn upkeep(&self, config: &SimConfig) -> f32 {
+        SIZE_UPKEEP * self.scale(config).powi(2)
+            + MAX_HEALTH_UPKEEP * self.max_health / BASE_MAX_HEALTH
+            + MAX_ENERGY_UPKEEP * self.max_energy / BASE_MAX_ENERGY
+            + POWER_UPKEEP * self.power
+            + VISION_UPKEEP * self.vision_radius / BASE_VISION_RADIUS
+    }
+
+    /// Checks every gene lies within the range mutation keeps it in.
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value, (min, max)) in [
+            ("size", self.size, SIZE_RANGE),
+            ("max_health", self.max_health, MAX_HEALTH_RANGE),
+            ("max_energy", self.max_energy, MAX_ENERGY_RANGE),
+            ("power", self.power, POWER_RANGE),
+            ("vision_radius", self.vision_radius, VISION_RADIUS_RANGE),
+        ] {
+            if !(min..=max).contains(&value) {
+                return Err(format!("{name} {value} is outside {min} to {max}"));
+            }
+        }
+        if !self.color.iter().all(|channel| (0.0..=1.0).contains(channel)) {
+            return Err(format!("colour {:?} is outside 0 to 1", self.color));
+        }
+        Ok(())
+    }
+
+    /// Body expressed by the two chromosomes' alleles: every gene is the average of both,
+    /// weighted by their dominance.
+    pub fn express(&self, dominance: &BodyDominance, other: &Genome, other_dominance: &BodyDominance) -> Genome {
+        let blend = |a: f32, da: f32, b: f32, db: f32| {
+            let total = da + db;
+            if total > 0.0 { (a * da + b * db) / total } else { (a + b) / 2.0 }
+        };
+        let mut color = [0.0; 3];
+        for (channel, (a, b)) in color.iter_mut().zip(self.color.iter().zip(other.color)) {
+            *channel = blend(*a, dominance.color, b, other_dominance.color);
+        }
+        Genome {
+            size: blend(self.size, dominance.size, other.size, other_dominance.size),
+            max_health: blend(self.max_health, dominance.max_health, other.max_health, other_dominance.max_health),
+            max_energy: blend(self.max_energy, dominance.max_energy, other.max_energy, other_dominance.max_energy),
+            power: blend(self.power, dominance.power, other.power, other_dominance.power),
+            color,
+            vision_radius: blend(
+                self.vision_radius,
+                dominance.vision_radius,
+                other.vision_radius,
+                other_dominance.vision_radius,
+            ),
+        }
+    }
+
+    /// Recombines two chromosomes' alleles: every gene comes, with its dominance, from one of them
+    /// at random.
+    pub fn recombine<R: Rng + ?Sized>(
+        &self,
+        dominance: &BodyDominance,
+        other: &Genome,
+        other_dominance: &BodyDominance,
+        rng: &mut R,
+    ) -> (Genome, BodyDominance) {
+        let mut genes = *self;
+        let mut genes_dominance = *dominance;
+        let mut pick = |gene: &mut f32, gene_dominance: &mut f32, other_gene: f32, other_gene_dominance: f32| {
+            if rng.random_bool(0.5) {
+                *gene = other_gene;
+                *gene_dominance = other_gene_dominance;
+            }
+        };
+        pick(&mut genes.size, &mut genes_dominance.size, other.size, other_dominance.size);
+        pick(&mut genes.max_health, &mut genes_dominance.max_health, other.max_health, other_dominance.max_health);
+        pick(&mut genes.max_energy, &mut genes_dominance.max_energy, other.max_energy, other_dominance.max_energy);
+        pick(&mut genes.power, &mut genes_dominance.power, other.power, other_dominance.power);
+        pick(
+            &mut genes.vision_radius,
+            &mut genes_dominance.vision_radius,
+            other.vision_radius,
+            other_dominance.vision_radius,
+        );
+        if rng.random_bool(0.5) {
+            genes.color = other.color;
+            genes_dominance.color = other_dominance.color;
+        }
+        (genes, genes_dominance)
+    }
+
+    /// Each gene mutates with `chance`, scaling by up to `amount` either way. Colour channels shift
+    /// by up to `amount` instead.
+    pub fn mutate<R: Rng + ?Sized>(&mut self, chance: f32, amount: f32, rng: &mut R) {
+        let mut scale = |gene: &mut f32, (min, max): (f32, f32)| {
+            if rng.random_range(0.0..1.0) < chance {
+                *gene = (*gene * (1.0 + rng.random_range(-amount..=amount))).clamp(min, max);
+            }
+        };
+        scale(&mut self.size, SIZE_RANGE);
+        scale(&mut self.max_health, MAX_HEALTH_RANGE);
+        scale(&mut self.max_energy, MAX_ENERGY_RANGE);
+        scale(&mut self.power, POWER_RANGE);
+        scale(&mut self.vision_radius, VISION_RADIUS_RANGE);
+        if rng.random_range(0.0..1.0) < chance {
+            for channel in self.color.iter_mut() {
+                *channel = (*channel + rng.random_range(-amount..=amount)).clamp(0.0, 1.0);
+            }
+        }
+    }
+
+    /// `self` with mutations applied.
+    pub fn mutated<R: Rng + ?Sized>(&self, chance: f32, amount: f32, rng: &mut R) -> Genome {
+        let mut genome = *self;
+        genome.mutate(chance, amount, rng);
+        genome
+    }
+}
+
+/// Dominance of each body gene on one chromosome, 0.0 to 1.0. The expressed body weighs both
+/// chromosomes' alleles by it.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BodyDominance {
+    pub size: f32,
+    pub max_health: f32,
+    pub max_energy: f32,
+    pub power: f32,
+    pub color: f32,
+    pub vision_radius: f32,
+}
+
+impl Default for BodyDominance {
+    fn default() -> Self {
+        Self {
+            size: 0.5,
+            max_health: 0.5,
+            max_energy: 0.5,
+            power: 0.5,
+            color: 0.5,
+            vision_radius: 0.5,
+        }
+    }
+}
+
+impl BodyDominance {
+    pub fn validate(&self) -> Result<(), String> {
+        let dominances = [
+            self.size,
+            self.max_health,
+            self.max_energy,
+            self.power,
+            self.color,
+            self.vision_radius,
+        ];
+        if !dominances.iter().all(|dominance| (0.0..=1.0).contains(dominance)) {
+            return Err(format!("dominance outside 0 to 1 in {self:?}"));
+        }
+        Ok(())
+    }
+
+    /// Each dominance mutates with `chance`, shifting by up to `amount` either way.
+    pub fn mutate<R: Rng + ?Sized>(&mut self, chance: f32, amount: f32, rng: &mut R) {
+        for dominance in [
+            &mut self.size,
+            &mut self.max_health,
+            &mut self.max_energy,
+            &mut self.power,
+            &mut self.color,
+            &mut self.vision_radius,
+        ] {
+            if rng.random_range(0.0..1.0) < chance {
+                *dominance = (*dominance + rng.random_range(-amount..=amount)).clamp(0.0, 1.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::brain::*;
+use crate::common::EnergyPool;
+use crate::config::SimConfig;
+use crate::craber::*;
+use crate::genetics::Genotype;
+use crate::genome::Genome;
+
+/// Bumped whenever the genome file layout changes. Files with a different version are rejected.
+pub const GENOME_FORMAT_VERSION: u32 = 5;
+pub const GENOME_DIR: &str = "genomes";
+
+#[derive(Message)]
+pub struct ExportGenomeEvent {
+    pub entity: Entity,
+}
+
+#[derive(Message)]
+pub struct ImportGenomeEvent {
+    pub path: String,
+}
+
+/// Neuron as stored in a genome file. Runtime values are not part of the genome.
+#[derive(Serialize, Deserialize)]
+pub struct NeuronGene {
+    pub neuron_type: NeuronType,
+    pub activation_function: ActivationFunction,
+    pub innovation: u64,
+}
+
+/// Versioned, human-readable (RON) representation of a `Brain` and the body genes it came with.
+/// Crabers are exported as expressed, imported crabers carry the genome on both chromosomes.
+/// Neuron ids in `connections` follow the same 0..99 / 100..199 / 200+ scheme as `Brain`.
+#[derive(Serialize, Deserialize)]
+pub struct BrainGenome {
+    pub format_version: u32,
+    pub inputs: Vec<NeuronGene>,
+    pub hidden_layers: Vec<NeuronGene>,
+    pub outputs: Vec<NeuronGene>,
+    pub connections: Vec<Connection>,
+    pub eyes: Vec<Eye>,
+    pub body: Genome,
+}
+
+impl BrainGenome {
+    pub fn from_brain(brain: &Brain, body: &Genome) -> Self {
+        let genes = |neurons: &[Neuron]| {
+            neurons
+                .iter()
+                .map(|n| NeuronGene {
+                    neuron_type: n.neuron_type,
+                    activation_function: n.activation_function,
+                    innovation: n.innovation,
+                })
+                .collect()
+        };
+        Self {
+            format_version: GENOME_FORMAT_VERSION,
+            inputs: genes(&brain.inputs),
+            hidden_layers: genes(&brain.hidden_layers),
+            outputs: genes(&brain.outputs),
+            connections: brain.connections.clone(),
+            eyes: brain.eyes.clone(),
+            body: *body,
+        }
+    }
+
+    pub fn into_brain(self) -> Result<(Brain, Genome), String> {
+        if self.format_version != GENOME_FORMAT_VERSION {
+            return Err(format!(
+                "unsupported genome format version {} (expected {})",
+                self.format_version, GENOME_FORMAT_VERSION
+            ));
+        }
+        let neurons = |genes: Vec<NeuronGene>| -> Vec<Neuron> {
+            genes
+                .into_iter()
+                .map(|g| Neuron {
+                    neuron_type: g.neuron_type,
+                    activation_function: g.activation_function,
+                    value: if g.neuron_type == NeuronType::AlwaysOn { 1.0 } else { 0.0 },
+                    innovation: g.innovation,
+                })
+                .collect()
+        };
+        let brain = Brain {
+            inputs: neurons(self.inputs),
+            hidden_layers: neurons(self.hidden_layers),
+            outputs: neurons(self.outputs),
+            connections: self.connections,
+            eyes: self.eyes,
+        };
+        brain.validate().map_err(|e| format!("invalid genome: {e}"))?;
+        self.body.validate().map_err(|e| format!("invalid body genes: {e}"))?;
+        Ok((brain, self.body))
+    }
+
+    pub fn to_ron_string(&self) -> Result<String, String> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| format!("failed to serialize genome: {e}"))
+    }
+
+    pub fn from_ron_str(text: &str) -> Result<Self, String> {
+        ron::from_str(text).map_err(|e| format!("failed to parse genome: {e}"))
+    }
+}
+
+/// Lists `.ron` genome files in `GENOME_DIR`, sorted by path.
+pub fn list_genome_files() -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(GENOME_DIR) else {
+        return Vec::new();
+    };
+    let mut files: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    files
+}
+
+pub fn export_genome(
+    mut export_events: MessageReader<ExportGenomeEvent>,
+    craber_query: Query<(&Brain, &Genome, &Generation)>,
+) {
+    for event in export_events.read() {
+        let Ok((brain, body, generation)) = craber_query.get(event.entity) else {
+            continue;
+        };
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let path = format!(
+            "{}/gen{}_{}.ron",
+            GENOME_DIR, generation.generation_id, timestamp
+        );
+        let result = BrainGenome::from_brain(brain, body)
+            .to_ron_string()
+            .and_then(|text| {
+                std::fs::create_dir_all(GENOME_DIR)
+                    .and_then(|_| std::fs::write(&path, text))
+                    .map_err(|e| format!("failed to write {path}: {e}"))
+            });
+        match result {
+            Ok(()) => info!("Exported genome to {}", path),
+            Err(e) => warn!("Could not export genome: {}", e),
+        }
+    }
+}
+
+pub fn import_genome(
+    mut import_events: MessageReader<ImportGenomeEvent>,
+    camera_query: Query<&Transform, With<Camera2d>>,
+    mut spawn_events: MessageWriter<SpawnEvent>,
+    mut innovations: ResMut<InnovationRegistry>,
+    mut energy_pool: ResMut<EnergyPool>,
+    config: Res<SimConfig>,
+    crabers_query: Query<&Craber>,
+) {
+    for event in import_events.read() {
+        let genome = std::fs::read_to_string(&event.path)
+            .map_err(|e| format!("failed to read {}: {e}", event.path))
+            .and_then(|text| BrainGenome::from_ron_str(&text))
+            .and_then(BrainGenome::into_brain);
+        let (mut brain, body) = match genome {
+            Ok(genome) => genome,
+            Err(e) => {
+                warn!("Could not import genome: {}", e);
+                continue;
+            }
+        };
+        // `spawn_craber` turns crabers away at the cap
+        if crabers_query.iter().len() >= config.max_crabers {
+            warn!("Could not import genome: the population is at max_crabers");
+            continue;
+        }
+        if !energy_pool.withdraw(100.0) {
+            warn!("Could not import genome: the energy pool is empty");
+            continue;
+        }
+        // Markings in the file come from the run that exported it
+        brain.restamp_innovations(&mut innovations);
+
+        // Drop the imported craber where the camera is looking
+        let position = camera_query
+            .iter()
+            .next()
+            .map(|t| t.translation.truncate().extend(0.0))
+            .unwrap_or(Vec3::ZERO);
+        spawn_events.write(SpawnEvent {
+            position,
+            roation: Quat::IDENTITY,
+            craber: Craber {},
+            generation: 0,
+            parents: Vec::new(),
+            genotype: Genotype::homozygous(brain.clone(), body),
+            new_brain: brain,
+            genome: body,
+            health: Health {
+                max_health: body.max_health,
+                health: body.max_health,
+            },
+            energy: Energy {
+                max_energy: body.max_energy,
+                energy: 100.,
+            },
+        });
+        info!("Imported genome from {}", event.path);
+    }
+}
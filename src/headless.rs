@@ -0,0 +1,119 @@
+use bevy::prelude::*;
+use std::time::Instant;
+
+use crate::cli::CliArgs;
+use crate::common::*;
+use crate::craber::*;
+use crate::food::Food;
+use crate::rng::SimRng;
+use crate::species::SpeciesTracker;
+
+/// Used when `--headless` is given without `--seconds` or `--ticks`.
+pub const HEADLESS_DEFAULT_SECONDS: f64 = 600.0;
+
+/// Limits and progress of a headless run. Only present when running with `--headless`.
+/// Each update advances the simulation by exactly one fixed timestep.
+#[derive(Resource)]
+pub struct HeadlessRun {
+    pub max_seconds: Option<f64>,
+    pub max_ticks: Option<u64>,
+    pub ticks: u64,
+    pub started: Instant,
+}
+
+impl HeadlessRun {
+    /// Returns `None` for a normal windowed run.
+    pub fn from_cli(cli: &CliArgs) -> Option<Self> {
+        if !cli.headless {
+            return None;
+        }
+        let max_seconds = if cli.seconds.is_none() && cli.ticks.is_none() {
+            Some(HEADLESS_DEFAULT_SECONDS)
+        } else {
+            cli.seconds
+        };
+        Some(Self {
+            max_seconds,
+            max_ticks: cli.ticks,
+            ticks: 0,
+            started: Instant::now(),
+        })
+    }
+
+    fn finished(&self, elapsed_secs: f64) -> bool {
+        self.max_ticks.is_some_and(|max| self.ticks >= max)
+            || self.max_seconds.is_some_and(|max| elapsed_secs >= max)
+    }
+}
+
+/// Counts ticks and exits once the run limit is reached, printing a summary of the final world.
+#[allow(clippy::too_many_arguments)]
+pub fn headless_progress(
+    mut run: ResMut<HeadlessRun>,
+    time: Res<Time>,
+    debug_info: Res<DebugInfo>,
+    sim_rng: Res<SimRng>,
+    species: Res<SpeciesTracker>,
+    energy_pool: Res<EnergyPool>,
+    stats: Res<SimulationStats>,
+    craber_query: Query<(&Generation, &CraberAge, &Energy), With<Craber>>,
+    food_query: Query<&Food>,
+    mut app_exit: MessageWriter<AppExit>,
+) {
+    run.ticks += 1;
+    let elapsed = time.elapsed_secs_f64();
+    if !run.finished(elapsed) {
+        return;
+    }
+
+    let wall_secs = run.started.elapsed().as_secs_f64();
+    let craber_count = craber_query.iter().len();
+    let max_generation = craber_query
+        .iter()
+        .map(|(generation, _, _)| generation.generation_id)
+        .max()
+        .unwrap_or(0);
+    let avg_generation = craber_query
+        .iter()
+        .map(|(generation, _, _)| generation.generation_id as f64)
+        .sum::<f64>()
+        / craber_count.max(1) as f64;
+    let oldest = craber_query
+        .iter()
+        .map(|(_, age, _)| age.0)
+        .fold(0.0, f32::max);
+    let craber_energy: f64 = craber_query.iter().map(|(_, _, energy)| energy.energy as f64).sum();
+    let food_energy: f64 = food_query.iter().map(|food| food.energy_value as f64).sum();
+    let total_energy = energy_pool.energy + craber_energy + food_energy;
+
+    println!("Headless run finished");
+    println!("  seed:            {}", sim_rng.seed());
+    println!("  ticks:           {}", run.ticks);
+    println!("  simulated time:  {:.1}s", elapsed);
+    println!(
+        "  wall time:       {:.1}s ({:.1}x real time)",
+        wall_secs,
+        elapsed / wall_secs.max(f64::EPSILON)
+    );
+    println!("  crabers:         {}", craber_count);
+    println!("  food:            {}", debug_info.food_count);
+    println!("  generation:      avg {:.2}, max {}", avg_generation, max_generation);
+    println!("  oldest craber:   {:.1}s", oldest);
+    println!(
+        "  species:         {} living, {} extinct",
+        species.living().count(),
+        species.extinct_count()
+    );
+    println!(
+        "  energy:          pool {:.1}, food {:.1}, crabers {:.1}, drift {:.4}",
+        energy_pool.energy,
+        food_energy,
+        craber_energy,
+        total_energy - energy_pool.total
+    );
+    println!(
+        "  gene transfers:  {} ({} connections)",
+        stats.gene_transfer_total, stats.transferred_connection_total
+    );
+    app_exit.write(AppExit::Success);
+}
@@ -0,0 +1,253 @@
+use bevy::prelude::*;
+use bevy_egui::{EguiContexts, egui};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+use crate::common::SelectedEntity;
+
+/// Dead crabers are pruned from the tree after this many deaths.
+const LINEAGE_PRUNE_INTERVAL: u32 = 1000;
+/// Ancestor levels shown by the family tree viewer.
+const MAX_TREE_DEPTH: usize = 12;
+
+/// Unique id of a craber in the `LineageTree`. Never reused within a run.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Lineage(pub u64);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CauseOfDeath {
+    Starvation,
+    Killed,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LineageRecord {
+    /// None for spawned or imported crabers, one for asexual and two for sexual offspring.
+    pub parents: Vec<u64>,
+    pub children: Vec<u64>,
+    pub generation: u32,
+    pub born_at: f64,
+    pub died_at: Option<f64>,
+    pub cause_of_death: Option<CauseOfDeath>,
+    /// Set while the craber is alive.
+    #[serde(skip)]
+    pub entity: Option<Entity>,
+}
+
+/// Ancestry of every living craber. Dead crabers are kept while they have living
+/// descendants, the rest is pruned periodically.
+#[derive(Resource, Clone, Default, Serialize, Deserialize)]
+pub struct LineageTree {
+    pub records: BTreeMap<u64, LineageRecord>,
+    next_id: u64,
+    deaths_since_prune: u32,
+}
+
+impl LineageTree {
+    pub fn record_birth(&mut self, parents: &[u64], generation: u32, born_at: f64, entity: Entity) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        for parent in parents {
+            if let Some(record) = self.records.get_mut(parent) {
+                record.children.push(id);
+            }
+        }
+        self.records.insert(
+            id,
+            LineageRecord {
+                parents: parents.to_vec(),
+                children: Vec::new(),
+                generation,
+                born_at,
+                died_at: None,
+                cause_of_death: None,
+                entity: Some(entity),
+            },
+        );
+        id
+    }
+
+    pub fn record_death(&mut self, id: u64, died_at: f64, cause: CauseOfDeath) {
+        if let Some(record) = self.records.get_mut(&id) {
+            record.died_at = Some(died_at);
+            record.cause_of_death = Some(cause);
+            record.entity = None;
+        }
+        self.deaths_since_prune += 1;
+        if self.deaths_since_prune >= LINEAGE_PRUNE_INTERVAL {
+            self.prune();
+        }
+    }
+
+    /// Drops dead crabers that have no living descendants.
+    pub fn prune(&mut self) {
+        let mut keep = HashSet::new();
+        let mut stack: Vec<u64> = self
+            .records
+            .iter()
+            .filter(|(_, record)| record.died_at.is_none())
+            .map(|(id, _)| *id)
+            .collect();
+        while let Some(id) = stack.pop() {
+            if keep.insert(id)
+                && let Some(record) = self.records.get(&id)
+            {
+                stack.extend(&record.parents);
+            }
+        }
+        self.records.retain(|id, _| keep.contains(id));
+        for record in self.records.values_mut() {
+            record.children.retain(|child| keep.contains(child));
+        }
+        self.deaths_since_prune = 0;
+    }
+
+    /// Number of living crabers descending from `id`.
+    pub fn living_descendants(&self, id: u64) -> usize {
+        let mut seen = HashSet::new();
+        let mut stack = vec![id];
+        let mut living = 0;
+        while let Some(id) = stack.pop() {
+            let Some(record) = self.records.get(&id) else {
+                continue;
+            };
+            for child in &record.children {
+                if seen.insert(*child) {
+                    stack.push(*child);
+                    if self.records.get(child).is_some_and(|c| c.died_at.is_none()) {
+                        living += 1;
+                    }
+                }
+            }
+        }
+        living
+    }
+
+    /// Offsets every recorded time by `offset` seconds.
+    pub fn shift_time(&mut self, offset: f64) {
+        for record in self.records.values_mut() {
+            record.born_at += offset;
+            if let Some(died_at) = record.died_at.as_mut() {
+                *died_at += offset;
+            }
+        }
+    }
+}
+
+/// One line of the tree: the craber's summary and, if it's alive, a button to select it.
+fn lineage_entry(ui: &mut egui::Ui, tree: &LineageTree, id: u64, now: f64, select: &mut Option<Entity>) {
+    let Some(record) = tree.records.get(&id) else {
+        ui.weak(format!("#{id} (pruned)"));
+        return;
+    };
+    ui.horizontal(|ui| {
+        let status = match (record.died_at, record.cause_of_death) {
+            (Some(died_at), Some(cause)) => format!("died {:.0}s ago ({:?})", now - died_at, cause),
+            (Some(died_at), None) => format!("died {:.0}s ago", now - died_at),
+            (None, _) => format!("alive, {:.0}s old", now - record.born_at),
+        };
+        ui.label(format!("#{id} gen {} - {status}", record.generation));
+        if let Some(entity) = record.entity
+            && ui.small_button("Select").clicked()
+        {
+            *select = Some(entity);
+        }
+    });
+}
+
+fn ancestors_tree(
+    ui: &mut egui::Ui,
+    tree: &LineageTree,
+    id: u64,
+    depth: usize,
+    now: f64,
+    select: &mut Option<Entity>,
+) {
+    let Some(record) = tree.records.get(&id) else {
+        return;
+    };
+    for parent in &record.parents {
+        if depth >= MAX_TREE_DEPTH {
+            ui.weak("...");
+            return;
+        }
+        // Header bodies are only built while open, so deep trees stay cheap
+        egui::CollapsingHeader::new(format!("#{parent}"))
+            .id_salt(("ancestor", id, *parent))
+            .show(ui, |ui| {
+                lineage_entry(ui, tree, *parent, now, select);
+                ancestors_tree(ui, tree, *parent, depth + 1, now, select);
+            });
+    }
+}
+
+fn descendants_tree(ui: &mut egui::Ui, tree: &LineageTree, id: u64, now: f64, select: &mut Option<Entity>) {
+    let Some(record) = tree.records.get(&id) else {
+        return;
+    };
+    for child in &record.children {
+        let living = tree.living_descendants(*child)
+            + usize::from(tree.records.get(child).is_some_and(|c| c.died_at.is_none()));
+        if living == 0 {
+            continue;
+        }
+        egui::CollapsingHeader::new(format!("#{child} ({living} living)"))
+            .id_salt(("descendant", id, *child))
+            .show(ui, |ui| {
+                lineage_entry(ui, tree, *child, now, select);
+                descendants_tree(ui, tree, *child, now, select);
+            });
+    }
+}
+
+/// Ancestors and living descendants of the selected craber.
+pub fn egui_family_tree(
+    mut contexts: EguiContexts,
+    mut selected: ResMut<SelectedEntity>,
+    lineage_query: Query<&Lineage>,
+    tree: Res<LineageTree>,
+    time: Res<Time>,
+    mut initialized: Local<bool>,
+) {
+    let Ok(ctx) = contexts.ctx_mut() else { return };
+
+    if !*initialized {
+        *initialized = true;
+        return;
+    }
+    let Some(lineage) = selected.entity.and_then(|e| lineage_query.get(e).ok()) else {
+        return;
+    };
+
+    let transparent_frame = egui::Frame::new()
+        .fill(egui::Color32::from_rgba_unmultiplied(20, 20, 25, 200))
+        .corner_radius(6.0)
+        .inner_margin(10.0);
+
+    let now = time.elapsed_secs_f64();
+    let mut select = None;
+    egui::Window::new("Family Tree")
+        .default_pos([330.0, 10.0])
+        .default_size([320.0, 300.0])
+        .resizable(true)
+        .collapsible(true)
+        .default_open(false)
+        .frame(transparent_frame)
+        .show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                lineage_entry(ui, &tree, lineage.0, now, &mut select);
+                ui.separator();
+                ui.label("Ancestors");
+                ancestors_tree(ui, &tree, lineage.0, 0, now, &mut select);
+                ui.separator();
+                ui.label(format!(
+                    "Living descendants: {}",
+                    tree.living_descendants(lineage.0)
+                ));
+                descendants_tree(ui, &tree, lineage.0, now, &mut select);
+            });
+        });
+    if select.is_some() {
+        selected.entity = select;
+    }
+}
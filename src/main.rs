@@ -1,12 +1,20 @@
 use avian2d::prelude::*;
 use bevy::{
-    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    diagnostic::{
+        DiagnosticsPlugin, DiagnosticsStore, FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin,
+    },
+    ecs::system::SystemParam,
+    log::LogPlugin,
     prelude::*,
-    time::{Timer, TimerMode},
+    scene::ScenePlugin,
+    time::{Timer, TimeUpdateStrategy, TimerMode},
 };
 use bevy_egui::{EguiContexts, EguiPlugin, EguiPrimaryContextPass, egui};
 use egui_plot::{Line, Plot, PlotPoints};
-use std::collections::VecDeque;
+use rand::RngExt;
+use std::collections::{HashSet, VecDeque};
+use std::f32::consts::PI;
+use std::time::Duration;
 
 mod craber;
 use craber::*;
@@ -19,19 +27,56 @@ use food::*;
 
 mod neural_viz;
 
+mod snapshot;
+use snapshot::*;
+
+mod parameters;
+use parameters::*;
+
+mod species;
+use species::*;
+
+mod lineage;
+use lineage::*;
+
+mod spatial;
+use spatial::*;
+
+mod pheromone;
+use pheromone::*;
+
+mod time_controls;
+use time_controls::*;
+
+mod genome;
+use genome::*;
+
+mod genetics;
+use genetics::*;
+
+mod genome_io;
+use genome_io::*;
+
+mod headless;
+use headless::*;
+
+mod cli;
+use cli::*;
+
+mod rng;
+use rng::*;
+
+mod config;
+use config::*;
+
 mod common;
 use bevy_pancam::{PanCam, PanCamPlugin};
 use common::*;
 
 const SOME_COLLISION_THRESHOLD: f32 = 20.0;
-const FOOD_SPAWN_RATE: f32 = 0.0004;
-const CRABER_SPAWN_RATE: f32 = 0.1;
-
-pub const MAX_FOOD_COUNT: usize = 10000;
 
-const GRAVITY: f32 = 0.0;
-
-const VISION_UPDATE_RATE: f32 = 0.01;
+/// Rate of the fixed simulation timestep.
+const SIMULATION_HZ: f64 = 60.0;
 
 #[derive(Resource, Default)]
 struct DebugVisionEnabled(bool);
@@ -46,8 +91,49 @@ const ENABLE_LEFT_MOUSE_BUTTON_DRAG: bool = true;
 const ENABLE_LEFT_MOUSE_BUTTON_DRAG: bool = false;
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins.set(WindowPlugin {
+    let cli = match CliArgs::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("{e}");
+            eprintln!("{USAGE}");
+            std::process::exit(2);
+        }
+    };
+    let config = match cli.sim_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+    };
+    if cli.print_config {
+        match config.to_ron_string() {
+            Ok(text) => println!("{text}"),
+            Err(e) => eprintln!("{e}"),
+        }
+        return;
+    }
+    let sim_rng = cli.seed.map_or_else(SimRng::from_entropy, SimRng::new);
+    let pheromones = PheromoneField::new(&config);
+    let energy_pool = EnergyPool::new(config.world_energy);
+
+    let mut app = App::new();
+    if let Some(run) = HeadlessRun::from_cli(&cli) {
+        // No window, renderer, input or UI: every update runs exactly one fixed timestep
+        // and updates run back to back
+        app.add_plugins(MinimalPlugins)
+            .add_plugins(TransformPlugin)
+            // Avian's collider setup depends on the scene spawner
+            .add_plugins(AssetPlugin::default())
+            .add_plugins(ScenePlugin)
+            .add_plugins(LogPlugin::default())
+            .add_plugins(DiagnosticsPlugin)
+            .add_plugins(FrameTimeDiagnosticsPlugin::default())
+            .insert_resource(TimeUpdateStrategy::FixedTimesteps(1))
+            .insert_resource(run)
+            .add_systems(Last, headless_progress);
+    } else {
+        app.add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
                 canvas: Some("#game-canvas".to_string()),
                 fit_canvas_to_parent: true,
@@ -59,61 +145,117 @@ fn main() {
         .add_plugins(EguiPlugin::default())
         .add_plugins(LogDiagnosticsPlugin::default())
         .add_plugins(FrameTimeDiagnosticsPlugin::default())
-        .add_plugins(PhysicsPlugins::default())
+        .insert_resource(DebugVisionEnabled::default())
+        .insert_resource(TimeControls::default())
+        .add_systems(Startup, setup_camera)
+        .add_systems(Update, entity_selection)
+        .add_systems(Update, highlight_selected_entity.after(entity_selection))
+        .add_systems(Update, update_selected_entity_info)
+        .add_systems(EguiPrimaryContextPass, egui_ui)
+        .add_systems(EguiPrimaryContextPass, egui_charts.after(egui_ui))
+        .add_systems(EguiPrimaryContextPass, egui_parameters.after(egui_charts))
+        .add_systems(EguiPrimaryContextPass, egui_time_controls.after(egui_parameters))
+        .add_systems(EguiPrimaryContextPass, egui_family_tree.after(egui_time_controls))
+        .add_systems(Update, time_control_hotkeys)
+        .add_systems(
+            RunFixedMainLoop,
+            apply_time_controls.in_set(RunFixedMainLoopSystems::BeforeFixedMainLoop),
+        )
+        .add_systems(Update, toggle_debug_vision)
+        .add_systems(Update, draw_vision_debug)
+        .add_systems(Startup, setup_pheromone_overlay)
+        .add_systems(Update, (toggle_pheromone_overlay, draw_pheromone_overlay).chain())
+        .add_systems(Update, snapshot_hotkeys.before(save_world_snapshot));
+    }
+
+    // Simulation systems run on the fixed timestep in one deterministic order, so a seed
+    // always replays the same population history. Physics runs in FixedPostUpdate.
+    app.add_plugins(PhysicsPlugins::default())
+        .insert_resource(Time::<Fixed>::from_hz(SIMULATION_HZ))
+        .insert_resource(sim_rng)
+        .insert_resource(config)
         .insert_resource(SelectedEntity::default())
+        .insert_resource(InnovationRegistry::default())
+        .insert_resource(SpeciesTracker::default())
+        .insert_resource(LineageTree::default())
+        .insert_resource(SpatialIndex::default())
+        .insert_resource(pheromones)
+        .insert_resource(energy_pool)
         .insert_resource(DebugInfo::default())
         .insert_resource(SimulationStats::new(600))
-        .insert_resource(DebugVisionEnabled::default())
         .add_message::<DespawnEvent>()
         .add_message::<SpawnEvent>()
         .add_message::<ReproduceEvent>()
         .add_message::<SexualReproduceRequestEvent>()
         .add_message::<SexualReproduceEvent>()
-        .add_message::<VisionEvent>()
-        .add_message::<LoseEnergyEvent>()
         .add_message::<LoseHealthEvent>()
         .add_message::<CraberCollisionEvent>()
+        .add_message::<GeneTransferEvent>()
         .add_message::<CraberAttackEvent>()
         .add_message::<CraberDespawnEvent>()
         .add_message::<FoodSpawnEvent>()
+        .add_message::<SaveSnapshotEvent>()
+        .add_message::<LoadSnapshotEvent>()
+        .add_message::<ExportGenomeEvent>()
+        .add_message::<ImportGenomeEvent>()
+        .add_message::<SavePresetEvent>()
+        .add_message::<LoadPresetEvent>()
         .add_systems(Startup, setup)
-        .add_systems(Update, entity_selection)
-        .add_systems(Update, highlight_selected_entity.after(entity_selection))
-        .add_systems(Update, update_selected_entity_info)
-        .add_systems(Update, update_debug_info)
-        .add_systems(EguiPrimaryContextPass, egui_ui)
-        .add_systems(Update, record_simulation_stats.after(update_debug_info))
-        .add_systems(EguiPrimaryContextPass, egui_charts.after(egui_ui))
-        .add_systems(Update, food_spawner)
-        .add_systems(Update, craber_spawner)
-        .add_systems(Update, do_collision)
-        .add_systems(Update, do_craber_collision)
-        .add_systems(Update, vision_update)
-        .add_systems(Update, apply_rotation)
-        .add_systems(Update, apply_water_drag)
-        .add_systems(Update, apply_kick)
-        .add_systems(Update, brain_update)
-        .add_systems(Update, craber_lose_energy)
-        .add_systems(Update, craber_lose_health)
-        .add_systems(Update, craber_attack_lose_health_add_energy)
-        .add_systems(Update, do_despawning)
-        // Ordered chains: reproduction pipeline and death pipeline
-        .add_systems(Update, energy_consumption.before(match_sexual_partners))
-        .add_systems(Update, match_sexual_partners.before(craber_sexual_reproduce))
-        .add_systems(Update, craber_sexual_reproduce.before(craber_reproduce))
-        .add_systems(Update, craber_reproduce.before(spawn_craber))
-        .add_systems(Update, spawn_craber)
-        .add_systems(Update, despawn_dead_crabers.before(craber_despawner))
-        .add_systems(Update, craber_despawner)
-        .add_systems(Update, spawn_food)
-        .add_systems(Update, toggle_debug_vision)
-        .add_systems(Update, draw_vision_debug)
-        .add_systems(Update, debug_check_finite)
+        .add_systems(
+            FixedUpdate,
+            (
+                (
+                    sync_spawn_timers,
+                    food_spawner,
+                    craber_spawner,
+                    do_collision,
+                    (do_craber_collision, do_gene_transfer).chain(),
+                    sense_contacts,
+                    apply_rotation,
+                    apply_water_drag,
+                    apply_kick,
+                    brain_clock,
+                    rebuild_spatial_index,
+                    diffuse_pheromones,
+                    vision_update,
+                    brain_update,
+                    emit_pheromones,
+                    update_vision_memory,
+                    apply_energy_deltas,
+                    craber_lose_health,
+                    craber_attack_lose_health_add_energy,
+                    do_despawning,
+                )
+                    .chain(),
+                // Reproduction pipeline, then death pipeline
+                (
+                    energy_consumption,
+                    match_sexual_partners,
+                    craber_sexual_reproduce,
+                    craber_reproduce,
+                    spawn_craber,
+                    despawn_dead_crabers,
+                    craber_despawner,
+                    spawn_food,
+                    debug_check_finite,
+                    update_debug_info,
+                    record_simulation_stats,
+                    update_species,
+                )
+                    .chain(),
+            )
+                .chain(),
+        )
+        .add_systems(Update, save_world_snapshot)
+        .add_systems(Update, load_world_snapshot.after(save_world_snapshot))
+        .add_systems(Update, export_genome)
+        .add_systems(Update, import_genome)
+        .add_systems(Update, save_preset)
+        .add_systems(Update, load_preset.after(save_preset))
         .run();
 }
 
-fn setup(mut commands: Commands) {
-    // setup
+fn setup_camera(mut commands: Commands) {
     let grab_buttons = if ENABLE_LEFT_MOUSE_BUTTON_DRAG {
         vec![MouseButton::Left, MouseButton::Right]
     } else {
@@ -127,69 +269,85 @@ fn setup(mut commands: Commands) {
         max_scale: 40.,
         ..Default::default()
     });
+}
+
+fn setup(
+    mut commands: Commands,
+    headless: Option<Res<HeadlessRun>>,
+    sim_rng: Res<SimRng>,
+    config: Res<SimConfig>,
+) {
+    // setup
+    info!("Simulation seed: {} (replay with --seed)", sim_rng.seed());
     commands.insert_resource(FoodSpawnTimer(Timer::from_seconds(
-        FOOD_SPAWN_RATE,
+        config.food_spawn_rate,
         TimerMode::Repeating,
     )));
     commands.insert_resource(CraberSpawnTimer(Timer::from_seconds(
-        CRABER_SPAWN_RATE,
+        config.craber_spawn_rate,
         TimerMode::Repeating,
     )));
-    commands.insert_resource(Gravity(Vec2::NEG_Y * GRAVITY));
-
-    commands
-        .spawn((
-            Sprite {
-                color: Color::srgb(0.5, 0.5, 0.5),
-                custom_size: Some(Vec2::new(WORLD_SIZE * 2.0, WALL_THICKNESS)),
-                ..default()
-            },
-            Transform::from_translation(Vec3::new(0.0, WORLD_SIZE, 0.0)),
-        ))
-        .insert(CollisionLayers::new([Layer::Wall], [Layer::Craber, Layer::Vision]))
-        .insert(EntityType::Wall)
-        .insert(RigidBody::Static)
-        .insert(Collider::rectangle(WORLD_SIZE * 2.0, WALL_THICKNESS / 2.0));
-
-    commands
-        .spawn((
-            Sprite {
-                color: Color::srgb(0.5, 0.5, 0.5),
-                custom_size: Some(Vec2::new(WORLD_SIZE * 2.0, WALL_THICKNESS)),
-                ..default()
-            },
-            Transform::from_translation(Vec3::new(0.0, -WORLD_SIZE, 0.0)),
-        ))
-        .insert(CollisionLayers::new([Layer::Wall], [Layer::Craber, Layer::Vision]))
-        .insert(EntityType::Wall)
-        .insert(RigidBody::Static)
-        .insert(Collider::rectangle(WORLD_SIZE * 2.0, WALL_THICKNESS / 2.0));
-    commands
-        .spawn((
-            Sprite {
+    commands.insert_resource(Gravity(Vec2::NEG_Y * config.gravity));
+
+    let world_size = config.world_size;
+    let wall_thickness = config.wall_thickness;
+
+    // (position, drawn size, collider size)
+    let walls = [
+        (
+            Vec3::new(0.0, world_size, 0.0),
+            Vec2::new(world_size * 2.0, wall_thickness),
+            Vec2::new(world_size * 2.0, wall_thickness / 2.0),
+        ),
+        (
+            Vec3::new(0.0, -world_size, 0.0),
+            Vec2::new(world_size * 2.0, wall_thickness),
+            Vec2::new(world_size * 2.0, wall_thickness / 2.0),
+        ),
+        (
+            Vec3::new(world_size, 0.0, 0.0),
+            Vec2::new(wall_thickness, world_size * 2.0),
+            Vec2::new(wall_thickness / 2.0, world_size * 2.0),
+        ),
+        (
+            Vec3::new(-world_size, 0.0, 0.0),
+            Vec2::new(wall_thickness, world_size * 2.0),
+            Vec2::new(wall_thickness / 2.0, world_size * 2.0),
+        ),
+    ];
+    for (translation, size, collider_size) in walls {
+        let mut wall = commands.spawn(Transform::from_translation(translation));
+        if headless.is_none() {
+            wall.insert(Sprite {
                 color: Color::srgb(0.5, 0.5, 0.5),
-                custom_size: Some(Vec2::new(WALL_THICKNESS, WORLD_SIZE * 2.0)),
+                custom_size: Some(size),
                 ..default()
-            },
-            Transform::from_translation(Vec3::new(WORLD_SIZE, 0.0, 0.0)),
-        ))
-        .insert(CollisionLayers::new([Layer::Wall], [Layer::Craber, Layer::Vision]))
-        .insert(EntityType::Wall)
-        .insert(RigidBody::Static)
-        .insert(Collider::rectangle(WALL_THICKNESS / 2.0, WORLD_SIZE * 2.0));
-    commands
-        .spawn((
-            Sprite {
-                color: Color::srgb(0.5, 0.5, 0.5),
-                custom_size: Some(Vec2::new(WALL_THICKNESS, WORLD_SIZE * 2.0)),
-                ..default()
-            },
-            Transform::from_translation(Vec3::new(-WORLD_SIZE, 0.0, 0.0)),
-        ))
-        .insert(CollisionLayers::new([Layer::Wall], [Layer::Craber, Layer::Vision]))
-        .insert(EntityType::Wall)
-        .insert(RigidBody::Static)
-        .insert(Collider::rectangle(WALL_THICKNESS / 2.0, WORLD_SIZE * 2.0));
+            });
+        }
+        wall.insert(CollisionLayers::new([Layer::Wall], [Layer::Craber]))
+            .insert(EntityType::Wall)
+            .insert(Wall {
+                half_size: collider_size / 2.0,
+            })
+            .insert(RigidBody::Static)
+            .insert(Collider::rectangle(collider_size.x, collider_size.y));
+    }
+}
+
+/// Keeps the spawn timers in step with spawn rates edited at runtime.
+fn sync_spawn_timers(
+    config: Res<SimConfig>,
+    mut food_timer: ResMut<FoodSpawnTimer>,
+    mut craber_timer: ResMut<CraberSpawnTimer>,
+) {
+    let food_duration = Duration::from_secs_f32(config.food_spawn_rate);
+    if food_timer.0.duration() != food_duration {
+        food_timer.0.set_duration(food_duration);
+    }
+    let craber_duration = Duration::from_secs_f32(config.craber_spawn_rate);
+    if craber_timer.0.duration() != craber_duration {
+        craber_timer.0.set_duration(craber_duration);
+    }
 }
 
 fn update_debug_info(
@@ -215,6 +373,8 @@ fn record_simulation_stats(
     mut stats: ResMut<SimulationStats>,
     craber_query: Query<(&CraberAge, &Generation, &Energy, &Health, &ChildrenCount), With<Craber>>,
     brain_query: Query<&Brain, With<Craber>>,
+    food_query: Query<&Food>,
+    energy_pool: Res<EnergyPool>,
 ) {
     stats.sample_timer.tick(time.delta());
     if stats.sample_timer.just_finished() {
@@ -332,6 +492,24 @@ fn record_simulation_stats(
         stats.death_counter = 0;
         stats.sexual_birth_counter = 0;
         stats.asexual_birth_counter = 0;
+        let gene_transfers = stats.gene_transfer_counter as f64;
+        let transferred_connections = stats.transferred_connection_counter as f64;
+        push_sample(&mut stats.gene_transfer_rate_history, cap, elapsed, gene_transfers);
+        push_sample(&mut stats.transferred_connections_history, cap, elapsed, transferred_connections);
+        stats.gene_transfer_counter = 0;
+        stats.transferred_connection_counter = 0;
+
+        // Energy budget, dying crabers included since they still hold their energy
+        let food_energy: f64 = food_query.iter().map(|food| food.energy_value as f64).sum();
+        push_sample(&mut stats.pool_energy_history, cap, elapsed, energy_pool.energy);
+        push_sample(&mut stats.food_energy_history, cap, elapsed, food_energy);
+        push_sample(&mut stats.craber_energy_history, cap, elapsed, total_energy);
+        push_sample(
+            &mut stats.total_energy_history,
+            cap,
+            elapsed,
+            energy_pool.energy + food_energy + total_energy,
+        );
     }
 }
 
@@ -353,9 +531,22 @@ fn plot_lines(ui: &mut egui::Ui, plot_id: &str, lines: &[(&str, &VecDeque<[f64;
         });
 }
 
+/// Species colour as an egui colour, so charts match the crabers on screen.
+fn species_color32(id: u32) -> egui::Color32 {
+    let color = species_color(id).to_srgba();
+    egui::Color32::from_rgb(
+        (color.red * 255.0) as u8,
+        (color.green * 255.0) as u8,
+        (color.blue * 255.0) as u8,
+    )
+}
+
 fn egui_charts(
     mut contexts: EguiContexts,
     stats: Res<SimulationStats>,
+    species: Res<SpeciesTracker>,
+    energy_pool: Res<EnergyPool>,
+    time: Res<Time>,
     mut initialized: Local<bool>,
 ) {
     let Ok(ctx) = contexts.ctx_mut() else { return };
@@ -499,14 +690,227 @@ fn egui_charts(
                 ("Sexual", &stats.sexual_birth_rate_history),
                 ("Asexual", &stats.asexual_birth_rate_history),
             ]);
+            ui.separator();
+            ui.label(format!(
+                "Gene Transfers ({} total, {} connections)",
+                stats.gene_transfer_total, stats.transferred_connection_total
+            ));
+            plot_lines(ui, "gene_transfer_rate", &[
+                ("Transfers", &stats.gene_transfer_rate_history),
+                ("Connections", &stats.transferred_connections_history),
+            ]);
+        });
+
+    // Energy window
+    egui::Window::new("Energy")
+        .default_pos([10.0, 685.0])
+        .default_size([300.0, 200.0])
+        .resizable(true)
+        .collapsible(true)
+        .default_open(false)
+        .frame(transparent_frame)
+        .show(ctx, |ui| {
+            let latest = |history: &VecDeque<[f64; 2]>| history.back().map_or(0.0, |sample| sample[1]);
+            let total = latest(&stats.total_energy_history);
+            egui::Grid::new("energy_table").show(ui, |ui| {
+                for (label, value) in [
+                    ("Pool", latest(&stats.pool_energy_history)),
+                    ("Food", latest(&stats.food_energy_history)),
+                    ("Crabers", latest(&stats.craber_energy_history)),
+                    ("Total", total),
+                    ("World energy", energy_pool.total),
+                    ("Drift", total - energy_pool.total),
+                ] {
+                    ui.label(label);
+                    ui.label(format!("{value:.2}"));
+                    ui.end_row();
+                }
+            });
+            plot_lines(ui, "energy_budget", &[
+                ("Pool", &stats.pool_energy_history),
+                ("Food", &stats.food_energy_history),
+                ("Crabers", &stats.craber_energy_history),
+                ("Total", &stats.total_energy_history),
+            ]);
+        });
+
+    // Species window
+    egui::Window::new("Species")
+        .default_pos([10.0, 660.0])
+        .default_size([300.0, 200.0])
+        .resizable(true)
+        .collapsible(true)
+        .default_open(false)
+        .frame(transparent_frame)
+        .show(ctx, |ui| {
+            let mut largest: Vec<&SpeciesRecord> = species.living().collect();
+            largest.sort_by_key(|record| std::cmp::Reverse(record.size));
+            largest.truncate(10);
+
+            ui.label(format!(
+                "Living: {}, extinct: {}",
+                species.living().count(),
+                species.extinct_count()
+            ));
+            plot_lines(ui, "species_count", &[("Species", &species.species_count_history)]);
+            ui.separator();
+            ui.label("Largest species");
+            Plot::new("species_sizes")
+                .view_aspect(2.0)
+                .show(ui, |plot_ui| {
+                    for record in &largest {
+                        let Some(history) = species.size_history.get(&record.id) else {
+                            continue;
+                        };
+                        let points: PlotPoints = PlotPoints::new(history.iter().copied().collect());
+                        plot_ui.line(
+                            Line::new(format!("Species {}", record.id), points)
+                                .color(species_color32(record.id)),
+                        );
+                    }
+                });
+            egui::Grid::new("species_table").striped(true).show(ui, |ui| {
+                ui.label("");
+                ui.label("Species");
+                ui.label("Size");
+                ui.label("Peak");
+                ui.label("Age");
+                ui.end_row();
+                for record in &largest {
+                    ui.colored_label(species_color32(record.id), "⏺");
+                    ui.label(record.id.to_string());
+                    ui.label(record.size.to_string());
+                    ui.label(record.peak_size.to_string());
+                    ui.label(format!("{:.0}s", time.elapsed_secs_f64() - record.born_at));
+                    ui.end_row();
+                }
+            });
         });
 }
 
+fn egui_parameters(
+    mut contexts: EguiContexts,
+    mut config: ResMut<SimConfig>,
+    mut save_preset: MessageWriter<SavePresetEvent>,
+    mut load_preset: MessageWriter<LoadPresetEvent>,
+    mut preset_name: Local<String>,
+    mut preset_path: Local<String>,
+    mut initialized: Local<bool>,
+) {
+    let Ok(ctx) = contexts.ctx_mut() else { return };
+
+    if !*initialized {
+        *initialized = true;
+        return;
+    }
+
+    let transparent_frame = egui::Frame::new()
+        .fill(egui::Color32::from_rgba_unmultiplied(20, 20, 25, 200))
+        .corner_radius(6.0)
+        .inner_margin(10.0);
+
+    egui::Window::new("Parameters")
+        .default_pos([10.0, 685.0])
+        .default_size([300.0, 200.0])
+        .resizable(true)
+        .collapsible(true)
+        .default_open(false)
+        .frame(transparent_frame)
+        .show(ctx, |ui| {
+            draw_parameters(
+                ui,
+                &mut config,
+                &mut preset_name,
+                &mut preset_path,
+                &mut save_preset,
+                &mut load_preset,
+            );
+        });
+}
+
+/// Message writers for the Inspector's action buttons
+#[derive(SystemParam)]
+struct InspectorActions<'w> {
+    save_snapshot: MessageWriter<'w, SaveSnapshotEvent>,
+    load_snapshot: MessageWriter<'w, LoadSnapshotEvent>,
+    export_genome: MessageWriter<'w, ExportGenomeEvent>,
+    import_genome: MessageWriter<'w, ImportGenomeEvent>,
+}
+
+fn color_swatch(ui: &mut egui::Ui, color: [f32; 3]) {
+    let [red, green, blue] = color.map(|c| (c * 255.0) as u8);
+    ui.colored_label(egui::Color32::from_rgb(red, green, blue), "⏺");
+}
+
+/// Both chromosome sets next to the expressed body, alleles with their dominance in brackets.
+fn draw_genetics(ui: &mut egui::Ui, genotype: &Genotype, genome: &Genome) {
+    let [a, b] = &genotype.0;
+    egui::Grid::new("genetics").striped(true).show(ui, |ui| {
+        for label in ["Gene", "Chromosome A", "Chromosome B", "Expressed"] {
+            ui.label(label);
+        }
+        ui.end_row();
+        let genes = |body: &Genome, dominance: &BodyDominance| {
+            [
+                (body.size, dominance.size),
+                (body.max_health, dominance.max_health),
+                (body.max_energy, dominance.max_energy),
+                (body.power, dominance.power),
+                (body.vision_radius, dominance.vision_radius),
+            ]
+        };
+        let expressed = [genome.size, genome.max_health, genome.max_energy, genome.power, genome.vision_radius];
+        let labels = ["Size", "Max health", "Max energy", "Power", "Vision radius"];
+        let rows = labels
+            .iter()
+            .zip(genes(&a.body, &a.body_dominance))
+            .zip(genes(&b.body, &b.body_dominance))
+            .zip(expressed);
+        for (((label, (a_value, a_dominance)), (b_value, b_dominance)), value) in rows {
+            ui.label(*label);
+            ui.label(format!("{a_value:.2} ({a_dominance:.2})"));
+            ui.label(format!("{b_value:.2} ({b_dominance:.2})"));
+            ui.label(format!("{value:.2}"));
+            ui.end_row();
+        }
+        ui.label("Colour");
+        ui.horizontal(|ui| {
+            color_swatch(ui, a.body.color);
+            ui.label(format!("({:.2})", a.body_dominance.color));
+        });
+        ui.horizontal(|ui| {
+            color_swatch(ui, b.body.color);
+            ui.label(format!("({:.2})", b.body_dominance.color));
+        });
+        color_swatch(ui, genome.color);
+        ui.end_row();
+    });
+
+    // Connection genes carried by one chromosome only are expressed when dominant
+    let innovations = |brain: &Brain| -> HashSet<u64> { brain.connections.iter().map(|c| c.innovation).collect() };
+    let (a_genes, b_genes) = (innovations(&a.brain), innovations(&b.brain));
+    let single = a
+        .brain
+        .connections
+        .iter()
+        .filter(|c| !b_genes.contains(&c.innovation))
+        .chain(b.brain.connections.iter().filter(|c| !a_genes.contains(&c.innovation)));
+    let (dominant, recessive): (Vec<&Connection>, Vec<&Connection>) = single.partition(|c| c.dominance >= 0.5);
+    ui.label(format!(
+        "Connection genes: {} on both chromosomes, {} dominant and {} hidden recessive on one",
+        a_genes.intersection(&b_genes).count(),
+        dominant.len(),
+        recessive.len()
+    ));
+}
+
 fn egui_ui(
     mut contexts: EguiContexts,
     selected: Res<SelectedEntity>,
     debug_info: Res<DebugInfo>,
-    brain_query: Query<&Brain>,
+    brain_query: Query<(&Brain, Option<&Species>, &Genome, &Genotype)>,
+    mut actions: InspectorActions,
+    mut genome_import_path: Local<String>,
     mut initialized: Local<bool>,
 ) {
     let Ok(ctx) = contexts.ctx_mut() else { return };
@@ -529,16 +933,26 @@ fn egui_ui(
         .collapsible(true)
         .frame(transparent_frame)
         .show(ctx, |ui| {
-            if let Some(_entity) = selected.entity {
+            if let Some(entity) = selected.entity {
                 ui.label(format!("Health: {:.2}", selected.health));
                 ui.label(format!("Energy: {:.2}", selected.energy));
                 ui.label(format!("Generation: {}", selected.generation));
+                if let Ok((_, Some(species), _, _)) = brain_query.get(entity) {
+                    ui.colored_label(species_color32(species.0), format!("Species: {}", species.0));
+                }
                 ui.label(format!("Age: {:.1}s", selected.age));
                 ui.label(format!("Children: {}", selected.children_count));
                 ui.label(format!(
                     "Nearest food angle: {:.2}",
                     selected.nearest_food_anlge
                 ));
+                if let Ok((_, _, genome, genotype)) = brain_query.get(entity) {
+                    ui.collapsing("Genetics", |ui| draw_genetics(ui, genotype, genome));
+                }
+                if brain_query.contains(entity) && ui.button("Export genome").clicked()
+                {
+                    actions.export_genome.write(ExportGenomeEvent { entity });
+                }
             } else {
                 ui.label("No craber selected");
             }
@@ -549,12 +963,44 @@ fn egui_ui(
             ui.label(format!("Total: {}", debug_info.entity_count));
             ui.label(format!("FPS: {:.1}", debug_info.fps));
             ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("Save world (F5)").clicked() {
+                    actions.save_snapshot.write(SaveSnapshotEvent);
+                }
+                if ui.button("Load world (F9)").clicked() {
+                    actions.load_snapshot.write(LoadSnapshotEvent);
+                }
+            });
+            ui.horizontal(|ui| {
+                // Files are only listed while the dropdown is open
+                egui::ComboBox::from_id_salt("genome_import_path")
+                    .selected_text(if genome_import_path.is_empty() {
+                        "Choose genome..."
+                    } else {
+                        genome_import_path.as_str()
+                    })
+                    .show_ui(ui, |ui| {
+                        for path in list_genome_files() {
+                            let label = path.clone();
+                            ui.selectable_value(&mut *genome_import_path, path, label);
+                        }
+                    });
+                if ui
+                    .add_enabled(!genome_import_path.is_empty(), egui::Button::new("Import genome"))
+                    .clicked()
+                {
+                    actions.import_genome.write(ImportGenomeEvent {
+                        path: genome_import_path.clone(),
+                    });
+                }
+            });
+            ui.separator();
             ui.label("Press P for vision debug");
         });
 
     // Right panel: neural network (only when a craber is selected)
     if let Some(entity) = selected.entity {
-        if let Ok(brain) = brain_query.get(entity) {
+        if let Ok((brain, _, _, _)) = brain_query.get(entity) {
             egui::SidePanel::right("neural_network")
                 .default_width(440.0)
                 .resizable(false)
@@ -613,6 +1059,7 @@ fn highlight_selected_entity(
     ring_query: Query<Entity, With<SelectionRing>>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    config: Res<SimConfig>,
 ) {
     if selected.entity == *prev_selected {
         return;
@@ -625,7 +1072,7 @@ fn highlight_selected_entity(
     if let Some(entity) = selected.entity {
         let ring = commands
             .spawn((
-                Mesh2d(meshes.add(Circle::new(CRABER_SIZE))),
+                Mesh2d(meshes.add(Circle::new(config.craber_size))),
                 MeshMaterial2d(materials.add(ColorMaterial::from(
                     Color::srgba(1.0, 1.0, 0.0, 0.35),
                 ))),
@@ -692,20 +1139,24 @@ fn update_selected_entity_info(
     }
 }
 
+/// Eaten food returns its energy to the pool, which pays it out to the eater in
+/// `apply_energy_deltas`.
+#[allow(clippy::too_many_arguments)]
 fn do_collision(
     _commands: Commands,
     collisions: Collisions,
     query: Query<(Entity, &Transform, &EntityType)>,
-    mut craber_query: Query<(Entity, &mut Energy)>,
+    mut craber_query: Query<(Entity, &mut EnergyDelta)>,
     food_query: Query<(Entity, &mut Food, &Transform)>,
+    mut energy_pool: ResMut<EnergyPool>,
     mut despawn_events: MessageWriter<DespawnEvent>,
-    mut vision_events: MessageWriter<VisionEvent>,
     mut craber_collision_events: MessageWriter<CraberCollisionEvent>,
 ) {
+    // Food touching several crabers is only eaten once
+    let mut eaten = HashSet::new();
     for contacts in collisions.iter() {
         let entity1 = contacts.collider1;
         let entity2 = contacts.collider2;
-        let manifolds = &contacts.manifolds;
         if let Ok((entity1, _, entity1_type)) = query.get(entity1) {
             if let Ok((entity2, _, entity2_type)) = query.get(entity2) {
                 match (entity1_type, entity2_type) {
@@ -717,74 +1168,26 @@ fn do_collision(
                     }
                     (EntityType::Craber, EntityType::Food) => {
                         if let Ok(mut craber) = craber_query.get_mut(entity1) {
-                            if let Ok(food) = food_query.get(entity2) {
-                                craber.1.energy += food.1.energy_value;
+                            if let Ok(food) = food_query.get(entity2)
+                                && eaten.insert(entity2)
+                            {
+                                energy_pool.deposit(food.1.energy_value as f64);
+                                craber.1.0 += food.1.energy_value;
                                 despawn_events.write(DespawnEvent { entity: entity2 });
                             }
                         }
                     }
                     (EntityType::Food, EntityType::Craber) => {
                         if let Ok(mut craber) = craber_query.get_mut(entity2) {
-                            if let Ok(food) = food_query.get(entity1) {
-                                craber.1.energy += food.1.energy_value;
+                            if let Ok(food) = food_query.get(entity1)
+                                && eaten.insert(entity1)
+                            {
+                                energy_pool.deposit(food.1.energy_value as f64);
+                                craber.1.0 += food.1.energy_value;
                                 despawn_events.write(DespawnEvent { entity: entity1 });
                             }
                         }
                     }
-                    (EntityType::Food, EntityType::Vision) => {
-                        vision_events.write(VisionEvent {
-                            vision_entity: entity2,
-                            entity: entity1,
-                            event_type: VisionEventType::Food,
-                            entity_id: 2,
-                            manifolds: manifolds.clone(),
-                        });
-                    }
-                    (EntityType::Vision, EntityType::Food) => {
-                        vision_events.write(VisionEvent {
-                            vision_entity: entity1,
-                            entity: entity2,
-                            event_type: VisionEventType::Food,
-                            entity_id: 1,
-                            manifolds: manifolds.clone(),
-                        });
-                    }
-                    (EntityType::Craber, EntityType::Vision) => {
-                        vision_events.write(VisionEvent {
-                            vision_entity: entity2,
-                            entity: entity1,
-                            event_type: VisionEventType::Craber,
-                            entity_id: 2,
-                            manifolds: manifolds.clone(),
-                        });
-                    }
-                    (EntityType::Vision, EntityType::Craber) => {
-                        vision_events.write(VisionEvent {
-                            vision_entity: entity1,
-                            entity: entity2,
-                            event_type: VisionEventType::Craber,
-                            entity_id: 1,
-                            manifolds: manifolds.clone(),
-                        });
-                    }
-                    (EntityType::Wall, EntityType::Vision) => {
-                        vision_events.write(VisionEvent {
-                            vision_entity: entity2,
-                            entity: entity1,
-                            event_type: VisionEventType::Wall,
-                            entity_id: 2,
-                            manifolds: manifolds.clone(),
-                        });
-                    }
-                    (EntityType::Vision, EntityType::Wall) => {
-                        vision_events.write(VisionEvent {
-                            vision_entity: entity1,
-                            entity: entity2,
-                            event_type: VisionEventType::Wall,
-                            entity_id: 1,
-                            manifolds: manifolds.clone(),
-                        });
-                    }
                     _ => {}
                 }
             }
@@ -794,21 +1197,23 @@ fn do_collision(
 
 pub fn do_craber_collision(
     mut craber_collision_events: MessageReader<CraberCollisionEvent>,
-    query: Query<(Entity, &Brain, &LinearVelocity, &AngularVelocity)>,
+    query: Query<(Entity, &Brain, &Genome, &LinearVelocity, &AngularVelocity)>,
+    config: Res<SimConfig>,
     mut craber_attack_events: MessageWriter<CraberAttackEvent>,
+    mut gene_transfer_events: MessageWriter<GeneTransferEvent>,
 ) {
     for craber_collision_event in craber_collision_events.read() {
-        if let Ok((entity_a, brain_a, velocity_a, angular_a)) =
+        if let Ok((entity_a, brain_a, genome_a, velocity_a, angular_a)) =
             query.get(craber_collision_event.entity_a)
         {
-            if let Ok((entity_b, brain_b, velocity_b, angular_b)) =
+            if let Ok((entity_b, brain_b, genome_b, velocity_b, angular_b)) =
                 query.get(craber_collision_event.entity_b)
             {
                 if brain_a.get_want_to_attack() > 0. || brain_b.get_want_to_attack() > 0. {
-                    let a_damaged =
-                        brain_b.get_want_to_attack() * 5. * velocity_b.length() - angular_b.0.abs();
-                    let b_damaged =
-                        brain_a.get_want_to_attack() * 5. * velocity_a.length() - angular_a.0.abs();
+                    let a_damaged = brain_b.get_want_to_attack() * 5. * genome_b.power * velocity_b.length()
+                        - angular_b.0.abs();
+                    let b_damaged = brain_a.get_want_to_attack() * 5. * genome_a.power * velocity_a.length()
+                        - angular_a.0.abs();
                     if a_damaged > 0. {
                         craber_attack_events.write(CraberAttackEvent {
                             attacking_craber_entity: entity_b,
@@ -826,9 +1231,118 @@ pub fn do_craber_collision(
                         });
                     }
                 }
+                // Willing crabers each pass genes to the other
+                if brain_a.get_want_gene_transfer() > config.gene_transfer_threshold
+                    && brain_b.get_want_gene_transfer() > config.gene_transfer_threshold
+                {
+                    gene_transfer_events.write(GeneTransferEvent {
+                        donor: entity_a,
+                        recipient: entity_b,
+                    });
+                    gene_transfer_events.write(GeneTransferEvent {
+                        donor: entity_b,
+                        recipient: entity_a,
+                    });
+                }
+            }
+        }
+    }
+}
+
+type GeneTransferQueryData<'a> = (
+    &'a mut Brain,
+    &'a mut CompiledBrain,
+    &'a mut Genotype,
+    &'a Energy,
+    &'a mut EnergyDelta,
+    &'a mut GeneTransferCooldown,
+    &'a mut CraberRng,
+);
+
+/// Copies a subgraph of the donor's expressed brain into one of the recipient's chromosome sets,
+/// then expresses the recipient's brain again. Transfers that don't change the expressed brain,
+/// e.g. of recessive genes, are dropped. The recipient pays `gene_transfer_cost`.
+pub fn do_gene_transfer(
+    mut gene_transfer_events: MessageReader<GeneTransferEvent>,
+    mut query: Query<GeneTransferQueryData>,
+    config: Res<SimConfig>,
+    mut stats: ResMut<SimulationStats>,
+) {
+    // Donors give the genes they had before this step's transfers
+    let transfers: Vec<(Entity, Brain)> = gene_transfer_events
+        .read()
+        .filter_map(|event| Some((event.recipient, query.get(event.donor).ok()?.0.clone())))
+        .collect();
+    for (recipient, donor_brain) in transfers {
+        let Ok((mut brain, mut compiled_brain, mut genotype, energy, mut energy_delta, mut cooldown, mut rng)) =
+            query.get_mut(recipient)
+        else {
+            continue;
+        };
+        if !cooldown.timer.is_finished() || energy.energy + energy_delta.0 < config.gene_transfer_cost {
+            continue;
+        }
+        let chromosome = rng.0.random_range(0..2);
+        let mut new_genotype = genotype.clone();
+        let copied = new_genotype.0[chromosome].brain.receive_genes(
+            &donor_brain,
+            config.gene_transfer_max_connections,
+            &mut rng.0,
+        );
+        if copied == 0 {
+            continue;
+        }
+        let (mut phenotype, _) = new_genotype.express();
+        let expressed = |brain: &Brain| {
+            let mut genes: Vec<(u64, u32, u32, bool)> = brain
+                .connections
+                .iter()
+                .map(|c| (c.innovation, c.weight.to_bits(), c.bias.to_bits(), c.enabled))
+                .collect();
+            genes.sort_unstable();
+            genes
+        };
+        if expressed(&phenotype) == expressed(&brain) {
+            continue;
+        }
+        *genotype = new_genotype;
+        phenotype.copy_neuron_values(&brain);
+        *compiled_brain = phenotype.compile();
+        *brain = phenotype;
+        energy_delta.0 -= config.gene_transfer_cost;
+        cooldown.timer.reset();
+        stats.gene_transfer_counter += 1;
+        stats.transferred_connection_counter += copied as u32;
+        stats.gene_transfer_total += 1;
+        stats.transferred_connection_total += copied as u64;
+    }
+}
+
+/// Decays the collision and attack signals, then raises them again for crabers that bumped into
+/// another craber or were attacked this step.
+pub fn sense_contacts(
+    mut craber_collision_events: MessageReader<CraberCollisionEvent>,
+    mut craber_attack_events: MessageReader<CraberAttackEvent>,
+    mut query: Query<(&mut RecentlyCollidedValue, &mut WasAttackedValue)>,
+    time: Res<Time>,
+) {
+    let decay = (-2.0 * time.delta_secs()).exp();
+    query.par_iter_mut().for_each(|(mut collided, mut attacked)| {
+        collided.0 *= decay;
+        attacked.0 *= decay;
+    });
+    for event in craber_collision_events.read() {
+        for entity in [event.entity_a, event.entity_b] {
+            if let Ok((mut collided, _)) = query.get_mut(entity) {
+                collided.0 = 1.0;
             }
         }
     }
+    for event in craber_attack_events.read() {
+        if let Ok((_, mut attacked)) = query.get_mut(event.attacked_craber_entity) {
+            attacked.0 = 1.0;
+        }
+    }
 }
 
 fn do_despawning(
@@ -847,33 +1361,35 @@ fn do_despawning(
 fn apply_rotation(
     mut query: Query<(Forces, &mut RotationAccumulator, &Brain), With<Craber>>,
     time: Res<Time>,
+    config: Res<SimConfig>,
 ) {
     let dt = time.delta_secs();
-    for (mut forces, mut accumulator, brain) in query.iter_mut() {
+    query.par_iter_mut().for_each(|(mut forces, mut accumulator, brain)| {
         let rotation_direction = brain.get_rotation(); // [-1, 1] direction
         let rotation_rate = brain.get_rotate_rate().max(0.0); // [0, ∞) rate
-        let effective_rate = 1.0 - (-rotation_rate * ROTATION_RATE_STEEPNESS).exp();
+        let effective_rate = 1.0 - (-rotation_rate * config.rotation_rate_steepness).exp();
 
         accumulator.0 += effective_rate * dt;
-        if accumulator.0 < ROTATION_THRESHOLD {
-            continue;
+        if accumulator.0 < config.rotation_threshold {
+            return;
         }
-        accumulator.0 -= ROTATION_THRESHOLD;
+        accumulator.0 -= config.rotation_threshold;
 
-        let angular_impulse = rotation_direction * effective_rate * MAX_ANGULAR_IMPULSE;
+        let angular_impulse = rotation_direction * effective_rate * config.max_angular_impulse;
         if angular_impulse.is_finite() {
             forces.apply_angular_impulse(angular_impulse);
         } else {
             warn!("apply_rotation: NaN angular_impulse! rot_dir={} eff_rate={} brain_rotation={} brain_rotate_rate={}",
                 rotation_direction, effective_rate, brain.get_rotation(), brain.get_rotate_rate());
         }
-    }
+    });
 }
 
 /// System 2: Water drag via direct velocity damping — guarantees convergence, no overflow
 fn apply_water_drag(
     mut query: Query<(&mut LinearVelocity, &mut AngularVelocity), With<Craber>>,
     time: Res<Time>,
+    config: Res<SimConfig>,
 ) {
     let dt = time.delta_secs();
     for (mut lin_vel, mut ang_vel) in query.iter_mut() {
@@ -881,316 +1397,354 @@ fn apply_water_drag(
         let speed = lin_vel.0.length();
         if speed > 0.0 && speed.is_finite() {
             // Quadratic feel: faster speeds get damped more aggressively
-            let damp_factor = (-LINEAR_DRAG_COEFFICIENT * speed * dt).exp();
+            let damp_factor = (-config.linear_drag_coefficient * speed * dt).exp();
             lin_vel.0 *= damp_factor;
         }
 
         // Angular drag: damp angular velocity directly each frame
         let w = ang_vel.0;
         if w.abs() > 0.0 && w.is_finite() {
-            let damp_factor = (-ANGULAR_DRAG_COEFFICIENT * w.abs() * dt).exp();
+            let damp_factor = (-config.angular_drag_coefficient * w.abs() * dt).exp();
             ang_vel.0 *= damp_factor;
         }
     }
 }
 
+type KickQueryData<'a> = (
+    Entity,
+    Forces,
+    &'a mut KickAccumulator,
+    &'a Transform,
+    &'a Brain,
+    &'a Genome,
+    &'a mut EnergyDelta,
+);
+
 /// System 3: Accumulator-gated kick impulse
 fn apply_kick(
-    mut query: Query<(Entity, Forces, &mut KickAccumulator, &Transform, &Brain), With<Craber>>,
+    mut query: Query<KickQueryData, With<Craber>>,
     time: Res<Time>,
-    mut lose_energy_events: MessageWriter<LoseEnergyEvent>,
+    config: Res<SimConfig>,
 ) {
     let dt = time.delta_secs();
-    for (entity, mut forces, mut accumulator, transform, brain) in query.iter_mut() {
-        let kick_rate = brain.get_kick_rate().max(0.0);
-        let effective_rate = 1.0 - (-kick_rate * KICK_RATE_STEEPNESS).exp();
-        let kick_strength = brain.get_kick_strength().max(0.0);
-        let effective_strength = 1.0 - (-kick_strength * KICK_STEEPNESS).exp();
-
-        accumulator.0 += effective_rate * dt;
-        if accumulator.0 < KICK_THRESHOLD {
-            continue;
-        }
-        accumulator.0 -= KICK_THRESHOLD;
-
-        let facing_dir = (transform.rotation * Vec3::NEG_Y).truncate();
-        let thrust = facing_dir * effective_strength * MAX_IMPULSE;
-        if !thrust.x.is_finite() || !thrust.y.is_finite() {
-            warn!("apply_kick: NaN thrust! entity={:?} facing_dir={:?} eff_strength={} rot={:?} kick_strength={} kick_rate={}",
-                entity, facing_dir, effective_strength, transform.rotation,
-                brain.get_kick_strength(), brain.get_kick_rate());
-            continue;
-        }
-        forces.apply_linear_impulse(thrust);
+    query.par_iter_mut().for_each(
+        |(entity, mut forces, mut accumulator, transform, brain, genome, mut energy_delta)| {
+            let kick_rate = brain.get_kick_rate().max(0.0);
+            let effective_rate = 1.0 - (-kick_rate * config.kick_rate_steepness).exp();
+            let kick_strength = brain.get_kick_strength().max(0.0);
+            let effective_strength = 1.0 - (-kick_strength * config.kick_steepness).exp();
+
+            accumulator.0 += effective_rate * dt;
+            if accumulator.0 < config.kick_threshold {
+                return;
+            }
+            accumulator.0 -= config.kick_threshold;
+
+            let facing_dir = (transform.rotation * Vec3::NEG_Y).truncate();
+            let thrust = facing_dir * effective_strength * genome.power * config.max_impulse;
+            if !thrust.x.is_finite() || !thrust.y.is_finite() {
+                warn!("apply_kick: NaN thrust! entity={:?} facing_dir={:?} eff_strength={} rot={:?} kick_strength={} kick_rate={}",
+                    entity, facing_dir, effective_strength, transform.rotation,
+                    brain.get_kick_strength(), brain.get_kick_rate());
+                return;
+            }
+            forces.apply_linear_impulse(thrust);
 
-        let energy_cost = effective_strength.powf(1.5) * KICK_ENERGY_MODIFIER;
-        lose_energy_events.write(LoseEnergyEvent {
-            entity,
-            energy_lost: energy_cost,
-        });
-    }
+            // Stronger bodies pay for their extra thrust
+            energy_delta.0 -= effective_strength.powf(1.5) * genome.power * config.kick_energy_modifier;
+        },
+    );
 }
 
+/// Senses the nearest food, craber and wall, lists every food and craber in range and casts the
+/// eyes' rays, for each craber whose brain ticks this step.
 pub fn vision_update(
-    mut query: Query<(&mut Vision, &GlobalTransform, &Collider, &ChildOf)>,
-    mut vision_events: MessageReader<VisionEvent>,
-    brain_query: Query<&Brain>,
+    mut vision_query: Query<(&mut Vision, &ChildOf)>,
+    craber_query: Query<(&Transform, &Brain, &BrainTicked)>,
+    index: Res<SpatialIndex>,
 ) {
-    for vision_event in vision_events.read() {
-        match vision_event.event_type {
-            VisionEventType::Food => {
-                if let Ok((mut vision, global_transform, _collider, _parent)) =
-                    query.get_mut(vision_event.vision_entity)
-                {
-                    let manifolds = &vision_event.manifolds;
-                    let mut min_distance = f32::MAX;
-                    let mut closest_point = Vec2::new(0.0, 0.0);
-
-                    for manifold in manifolds {
-                        for contact in &manifold.points {
-                            if vision_event.entity_id == 1 {
-                                let distance = contact.anchor1.length() - contact.penetration;
-                                if distance < min_distance {
-                                    min_distance = distance;
-                                    closest_point = contact.anchor1;
-                                }
-                            } else {
-                                let distance = contact.anchor2.length() - contact.penetration;
-                                if distance < min_distance {
-                                    min_distance = distance;
-                                    closest_point = contact.anchor2;
-                                }
-                            }
-                        }
-                    }
-                    if !closest_point.x.is_finite() || !closest_point.y.is_finite() || !min_distance.is_finite() {
-                        continue;
-                    }
-                    if min_distance > 0.0 {
-                        closest_point = closest_point / min_distance;
-                    }
-                    vision.entities_in_vision.push(vision_event.entity);
-                    let vision_direction = global_transform.rotation().mul_vec3(Vec3::Y);
-                    let craber_direction = vision_direction;
-                    vision.nearest_food_distance = min_distance;
-                    vision.nearest_food_direction = -angle_direction_between_vectors(
-                        craber_direction,
-                        Vec3::new(closest_point.x, closest_point.y, 0.),
-                    );
-                    vision.see_food = true;
-                }
+    vision_query.par_iter_mut().for_each(|(mut vision, child_of)| {
+        let craber = child_of.parent();
+        let Ok((transform, brain, ticked)) = craber_query.get(craber) else {
+            return;
+        };
+        if !ticked.0 {
+            return;
+        }
+        let position = transform.translation.truncate();
+        let facing = transform.rotation.mul_vec3(Vec3::Y);
+        let direction_to =
+            |target: Vec2| -angle_direction_between_vectors(facing, (target - position).extend(0.));
+        let radius = vision.radius;
+
+        vision.no_see_food();
+        vision.no_see_craber();
+        vision.no_see_wall();
+
+        // Distances are measured to the surface of the seen body, like a sensor contact
+        let mut nearest_food: Option<(Vec2, f32)> = None;
+        for (entry, distance) in index.food_within(position, radius) {
+            vision.visible_food.push(entry.entity);
+            if nearest_food.is_none_or(|(_, nearest)| distance < nearest) {
+                nearest_food = Some((entry.position, distance));
             }
-            VisionEventType::Craber => {
-                if let Ok((mut vision, global_transform, _collider, _parent)) =
-                    query.get_mut(vision_event.vision_entity)
-                {
-                    let manifolds = &vision_event.manifolds;
-                    let mut min_distance = f32::MAX;
-                    let mut closest_point = Vec2::new(0.0, 0.0);
-
-                    for manifold in manifolds {
-                        for contact in &manifold.points {
-                            if vision_event.entity_id == 1 {
-                                let distance = contact.anchor1.length() - contact.penetration;
-                                if distance < min_distance {
-                                    min_distance = distance;
-                                    closest_point = contact.anchor1;
-                                }
-                            } else {
-                                let distance = contact.anchor2.length() - contact.penetration;
-                                if distance < min_distance {
-                                    min_distance = distance;
-                                    closest_point = contact.anchor2;
-                                }
-                            }
-                        }
-                    }
-                    if !closest_point.x.is_finite() || !closest_point.y.is_finite() || !min_distance.is_finite() {
-                        continue;
-                    }
-                    if min_distance > 0.0 {
-                        closest_point = closest_point / min_distance;
-                    }
-                    vision.entities_in_vision.push(vision_event.entity);
-                    let vision_direction = global_transform.rotation().mul_vec3(Vec3::Y);
-                    let craber_direction = vision_direction;
-                    vision.nearest_craber_distance = min_distance;
-                    vision.nearest_craber_direction = -angle_direction_between_vectors(
-                        craber_direction,
-                        Vec3::new(closest_point.x, closest_point.y, 0.),
-                    );
-                    vision.see_craber = true;
-
-                    // Compute genetic closeness between parent craber and seen craber
-                    let parent_entity = _parent.parent();
-                    if let (Ok(parent_brain), Ok(seen_brain)) =
-                        (brain_query.get(parent_entity), brain_query.get(vision_event.entity))
-                    {
-                        vision.nearest_craber_genetic_closeness =
-                            parent_brain.genetic_closeness(seen_brain);
-                    }
-                }
+        }
+        if let Some((food_position, distance)) = nearest_food {
+            vision.nearest_food_distance = distance;
+            vision.nearest_food_direction = direction_to(food_position);
+            vision.see_food = true;
+        }
+
+        let mut nearest_craber: Option<(Entity, Vec2, f32)> = None;
+        for (entry, distance) in index.crabers_within(position, radius, craber) {
+            vision.visible_crabers.push(entry.entity);
+            if nearest_craber.is_none_or(|(_, _, nearest)| distance < nearest) {
+                nearest_craber = Some((entry.entity, entry.position, distance));
             }
-            VisionEventType::Wall => {
-                if let Ok((mut vision, global_transform, _collider, _parent)) =
-                    query.get_mut(vision_event.vision_entity)
-                {
-                    let manifolds = &vision_event.manifolds;
-                    let mut min_distance = f32::MAX;
-                    let mut closest_point = Vec2::new(0.0, 0.0);
-
-                    for manifold in manifolds {
-                        for contact in &manifold.points {
-                            if vision_event.entity_id == 1 {
-                                let distance = contact.anchor1.length() - contact.penetration;
-                                if distance < min_distance {
-                                    min_distance = distance;
-                                    closest_point = contact.anchor1;
-                                }
-                            } else {
-                                let distance = contact.anchor2.length() - contact.penetration;
-                                if distance < min_distance {
-                                    min_distance = distance;
-                                    closest_point = contact.anchor2;
-                                }
-                            }
-                        }
-                    }
-                    if !closest_point.x.is_finite() || !closest_point.y.is_finite() || !min_distance.is_finite() {
-                        continue;
-                    }
-                    if min_distance > 0.0 {
-                        closest_point = closest_point / min_distance;
-                    }
-                    let vision_direction = global_transform.rotation().mul_vec3(Vec3::Y);
-                    let craber_direction = vision_direction;
-                    vision.nearest_wall_distance = min_distance;
-                    vision.nearest_wall_direction = -angle_direction_between_vectors(
-                        craber_direction,
-                        Vec3::new(closest_point.x, closest_point.y, 0.),
-                    );
-                    vision.see_wall = true;
-                }
+        }
+        if let Some((seen, craber_position, distance)) = nearest_craber {
+            vision.nearest_craber_distance = distance;
+            vision.nearest_craber_direction = direction_to(craber_position);
+            vision.see_craber = true;
+            if let Ok((_, seen_brain, _)) = craber_query.get(seen) {
+                vision.nearest_craber_genetic_closeness = brain.genetic_closeness(seen_brain);
             }
         }
-    }
+
+        if let Some((wall_point, distance)) = index.nearest_wall(position, radius) {
+            vision.nearest_wall_distance = distance;
+            vision.nearest_wall_direction = direction_to(wall_point);
+            vision.see_wall = true;
+        }
+
+        // Eyes look relative to the kick direction, which is the craber's -Y
+        let forward = transform.rotation.mul_vec3(Vec3::NEG_Y).truncate();
+        vision.eye_hits = [None; MAX_EYES as usize];
+        for eye in &brain.eyes {
+            let direction = Vec2::from_angle(-eye.angle).rotate(forward);
+            vision.eye_hits[eye.slot as usize] = index.ray_cast(position, direction, eye.range, craber);
+        }
+    });
 }
 
-pub fn brain_update(
-    mut query: Query<(
-        Entity,
-        &mut Brain,
-        &mut Craber,
-        &mut BrainTickAccumulator,
-        &Children,
-        &mut LastReproducedValue,
-        &Health,
-        &Energy,
-        &mut CraberAge,
-    )>,
-    mut vision_query: Query<(&mut Vision, &Transform)>,
+/// Brain ticks per second, set by the brain's ModifyBrainInterval output.
+fn brain_tick_rate(brain: &Brain, config: &SimConfig) -> f32 {
+    let modify_output = brain.get_modify_brain_interval().clamp(0.0, 1.0);
+    config.brain_tick_min_rate + modify_output * (config.brain_tick_max_rate - config.brain_tick_min_rate)
+}
+
+/// Decides which brains tick this step, ahead of `vision_update` so only those crabers look around.
+pub fn brain_clock(
+    mut query: Query<(&Brain, &mut BrainTickAccumulator, &mut BrainTicked, &mut CraberAge)>,
     time: Res<Time>,
-    mut lose_energy_events: MessageWriter<LoseEnergyEvent>,
+    config: Res<SimConfig>,
 ) {
     let dt = time.delta_secs();
-    for (entity, mut brain, _craber, mut accumulator, children, mut last_reproduced, health, energy, mut age) in query.iter_mut() {
-        let modify_output = brain.get_modify_brain_interval().clamp(0.0, 1.0);
-        let effective_rate =
-            BRAIN_TICK_MIN_RATE + modify_output * (BRAIN_TICK_MAX_RATE - BRAIN_TICK_MIN_RATE);
-
-        accumulator.0 += effective_rate * dt;
+    query.par_iter_mut().for_each(|(brain, mut accumulator, mut ticked, mut age)| {
+        accumulator.0 += brain_tick_rate(brain, &config) * dt;
         age.0 += dt; // Track real elapsed time, independent of brain tick rate
-        if accumulator.0 < 1.0 {
-            continue;
+        ticked.0 = accumulator.0 >= 1.0;
+        if ticked.0 {
+            accumulator.0 -= 1.0;
         }
-        accumulator.0 -= 1.0;
+    });
+}
 
-        let interval_normalized = (BRAIN_TICK_MIN_RATE / effective_rate).clamp(0.0, 1.0);
-        brain.update_input(NeuronType::BrainInterval, interval_normalized);
+type BrainQueryData<'a> = (
+    &'a mut Brain,
+    &'a mut CompiledBrain,
+    &'a BrainTicked,
+    &'a Children,
+    &'a mut LastReproducedValue,
+    &'a RecentlyCollidedValue,
+    &'a WasAttackedValue,
+    &'a Transform,
+    &'a LinearVelocity,
+    &'a AngularVelocity,
+    &'a Health,
+    &'a Energy,
+    &'a CraberAge,
+    &'a mut EnergyDelta,
+);
 
-        let mut vision = vision_query.get_mut(children[0]).unwrap().0;
-        if vision.see_food {
-            brain.update_input(NeuronType::NearestFoodAngle, vision.nearest_food_direction);
-            brain.update_input(
-                NeuronType::NearestFoodDistance,
-                vision.nearest_food_distance,
-            );
-            vision.food_seen_timer = VISION_UPDATE_RATE;
-            vision.no_see_food();
-        } else {
-            vision.food_seen_timer -= dt;
-            if vision.food_seen_timer <= 0.0 {
+pub fn brain_update(
+    mut query: Query<BrainQueryData, With<Craber>>,
+    vision_query: Query<&Vision>,
+    pheromones: Res<PheromoneField>,
+    time: Res<Time>,
+    config: Res<SimConfig>,
+) {
+    let dt = time.delta_secs();
+    query.par_iter_mut().for_each(
+        |(
+            mut brain,
+            mut compiled,
+            ticked,
+            children,
+            mut last_reproduced,
+            recently_collided,
+            was_attacked,
+            transform,
+            linear_vel,
+            angular_vel,
+            health,
+            energy,
+            age,
+            mut energy_delta,
+        )| {
+            if !ticked.0 {
+                return;
+            }
+            let effective_rate = brain_tick_rate(&brain, &config);
+
+            let interval_normalized = (config.brain_tick_min_rate / effective_rate).clamp(0.0, 1.0);
+            brain.update_input(NeuronType::BrainInterval, interval_normalized);
+
+            // An object that went out of sight stays in the inputs until its timer runs out
+            let vision = vision_query.get(children[0]).unwrap();
+            if vision.see_food {
+                brain.update_input(NeuronType::NearestFoodAngle, vision.nearest_food_direction);
+                brain.update_input(
+                    NeuronType::NearestFoodDistance,
+                    vision.nearest_food_distance,
+                );
+            } else if vision.food_seen_timer - dt <= 0.0 {
                 brain.update_input(NeuronType::NearestFoodAngle, 0.0);
                 brain.update_input(NeuronType::NearestFoodDistance, 0.0);
             }
-        }
-        if vision.see_craber {
-            brain.update_input(
-                NeuronType::NearestCraberAngle,
-                vision.nearest_craber_direction,
-            );
-            brain.update_input(
-                NeuronType::NearestCraberDistance,
-                vision.nearest_craber_distance,
-            );
-            brain.update_input(
-                NeuronType::NearestCraberGeneticCloseness,
-                vision.nearest_craber_genetic_closeness,
-            );
-            vision.craber_seen_timer = VISION_UPDATE_RATE;
-            vision.no_see_craber();
-        } else {
-            vision.craber_seen_timer -= dt;
-            if vision.craber_seen_timer <= 0.0 {
+            if vision.see_craber {
+                brain.update_input(
+                    NeuronType::NearestCraberAngle,
+                    vision.nearest_craber_direction,
+                );
+                brain.update_input(
+                    NeuronType::NearestCraberDistance,
+                    vision.nearest_craber_distance,
+                );
+                brain.update_input(
+                    NeuronType::NearestCraberGeneticCloseness,
+                    vision.nearest_craber_genetic_closeness,
+                );
+            } else if vision.craber_seen_timer - dt <= 0.0 {
                 brain.update_input(NeuronType::NearestCraberAngle, 0.0);
                 brain.update_input(NeuronType::NearestCraberDistance, 0.0);
                 brain.update_input(NeuronType::NearestCraberGeneticCloseness, 0.0);
             }
-        }
-        if vision.see_wall {
-            brain.update_input(
-                NeuronType::NearestWallAngle,
-                vision.nearest_wall_direction,
-            );
-            brain.update_input(
-                NeuronType::NearestWallDistance,
-                vision.nearest_wall_distance,
-            );
-            vision.wall_seen_timer = VISION_UPDATE_RATE;
-            vision.no_see_wall();
-        } else {
-            vision.wall_seen_timer -= dt;
-            if vision.wall_seen_timer <= 0.0 {
+            if vision.see_wall {
+                brain.update_input(
+                    NeuronType::NearestWallAngle,
+                    vision.nearest_wall_direction,
+                );
+                brain.update_input(
+                    NeuronType::NearestWallDistance,
+                    vision.nearest_wall_distance,
+                );
+            } else if vision.wall_seen_timer - dt <= 0.0 {
                 brain.update_input(NeuronType::NearestWallAngle, 0.0);
                 brain.update_input(NeuronType::NearestWallDistance, 0.0);
             }
-        }
-        // Feed health/energy/age inputs (normalized 0-1)
-        brain.update_input(NeuronType::CraberHealth, health.health / health.max_health);
-        brain.update_input(NeuronType::CraberEnergy, energy.energy / energy.max_energy);
-        brain.update_input(NeuronType::CraberAge, 1.0 - (-0.01 * age.0).exp());
+            // Eyes have no memory, they report what their ray hits this tick
+            for slot in 0..MAX_EYES {
+                let Some(range) = brain.eyes.iter().find(|eye| eye.slot == slot).map(|eye| eye.range) else {
+                    continue;
+                };
+                let hit = vision.eye_hits[slot as usize];
+                let closeness = hit.map_or(0.0, |hit| 1.0 - hit.distance / range);
+                let sees = |kind: EyeHitKind| if hit.is_some_and(|hit| hit.kind == kind) { 1.0 } else { 0.0 };
+                brain.update_input(NeuronType::EyeDistance(slot), closeness);
+                brain.update_input(NeuronType::EyeFood(slot), sees(EyeHitKind::Food));
+                brain.update_input(NeuronType::EyeCraber(slot), sees(EyeHitKind::Craber));
+                brain.update_input(NeuronType::EyeWall(slot), sees(EyeHitKind::Wall));
+            }
 
-        // Decay and feed LastReproduced input
-        last_reproduced.0 *= 1.0 - 0.5 * dt;
-        brain.update_input(NeuronType::LastReproduced, last_reproduced.0);
+            // Feed health/energy/age inputs (normalized 0-1)
+            brain.update_input(NeuronType::CraberHealth, health.health / health.max_health);
+            brain.update_input(NeuronType::CraberEnergy, energy.energy / energy.max_energy);
+            brain.update_input(NeuronType::CraberAge, 1.0 - (-0.01 * age.0).exp());
+
+            // Decay and feed LastReproduced input
+            last_reproduced.0 *= 1.0 - 0.5 * dt;
+            brain.update_input(NeuronType::LastReproduced, last_reproduced.0);
+
+            // Motion, relative to the kick direction; positive means to the right like the angle inputs
+            let speed = linear_vel.length();
+            let forward = transform.rotation.mul_vec3(Vec3::NEG_Y).truncate();
+            let velocity_direction = if speed > 1e-3 { -forward.angle_to(linear_vel.0) / PI } else { 0.0 };
+            brain.update_input(NeuronType::CraberSpeed, 1.0 - (-speed / 100.0).exp());
+            brain.update_input(NeuronType::CraberAngularVelocity, (-angular_vel.0).tanh());
+            brain.update_input(NeuronType::VelocityDirection, velocity_direction);
+            brain.update_input(NeuronType::RecentlyCollided, recently_collided.0);
+            brain.update_input(NeuronType::WasAttacked, was_attacked.0);
+
+            let position = transform.translation.truncate();
+            for channel in 0..PHEROMONE_CHANNELS {
+                let concentration = pheromones.concentration(channel as usize, position);
+                let gradient = pheromones.gradient(channel as usize, position);
+                let gradient_direction =
+                    if gradient.length_squared() > 1e-12 { -forward.angle_to(gradient) / PI } else { 0.0 };
+                brain.update_input(NeuronType::PheromoneConcentration(channel), concentration / (1.0 + concentration));
+                brain.update_input(NeuronType::PheromoneGradient(channel), gradient_direction);
+            }
 
-        brain.feed_forward();
+            compiled.feed_forward(&mut brain, config.brain_evaluation, config.brain_relaxation_steps);
 
-        lose_energy_events.write(LoseEnergyEvent {
-            entity,
-            energy_lost: BRAIN_TICK_ENERGY_COST,
-        });
-    }
+            energy_delta.0 -= config.brain_tick_energy_cost;
+        },
+    );
 }
 
-pub fn craber_lose_energy(
-    mut lose_energy_events: MessageReader<LoseEnergyEvent>,
-    mut query: Query<&mut Energy>,
+/// Restarts or runs down the sight timers of crabers whose brain ticked this step.
+pub fn update_vision_memory(
+    mut vision_query: Query<(&mut Vision, &ChildOf)>,
+    ticked_query: Query<&BrainTicked>,
+    time: Res<Time>,
+    config: Res<SimConfig>,
 ) {
-    for lose_energy_event in lose_energy_events.read() {
-        if let Ok(mut energy) = query.get_mut(lose_energy_event.entity) {
-            energy.energy -= lose_energy_event.energy_lost;
+    let dt = time.delta_secs();
+    vision_query.par_iter_mut().for_each(|(mut vision, child_of)| {
+        if !ticked_query.get(child_of.parent()).is_ok_and(|ticked| ticked.0) {
+            return;
+        }
+        if vision.see_food {
+            vision.food_seen_timer = config.vision_update_rate;
+        } else {
+            vision.food_seen_timer -= dt;
+        }
+        if vision.see_craber {
+            vision.craber_seen_timer = config.vision_update_rate;
+        } else {
+            vision.craber_seen_timer -= dt;
+        }
+        if vision.see_wall {
+            vision.wall_seen_timer = config.vision_update_rate;
+        } else {
+            vision.wall_seen_timer -= dt;
         }
+    });
+}
+
+/// Applies the energy changes collected in `EnergyDelta` this step, settling each with the energy
+/// pool. Gains stop at `max_energy`, the rest stays in the pool, and spending stops at empty.
+/// Runs sequentially so the pool sums in the same order every run.
+pub fn apply_energy_deltas(
+    mut query: Query<(&mut Energy, &mut EnergyDelta)>,
+    mut energy_pool: ResMut<EnergyPool>,
+) {
+    for (mut energy, mut energy_delta) in query.iter_mut() {
+        if energy_delta.0 > 0.0 {
+            let before = energy.energy;
+            let after = (before + energy_delta.0).min(energy.max_energy.max(before));
+            if after > before && energy_pool.withdraw(after as f64 - before as f64) {
+                energy.energy = after;
+            }
+        } else if energy_delta.0 < 0.0 {
+            // Crabers can't spend more than they hold
+            let before = energy.energy;
+            let after = (before + energy_delta.0).max(0.0).min(before);
+            energy.energy = after;
+            energy_pool.deposit(before as f64 - after as f64);
+        }
+        energy_delta.0 = 0.0;
     }
 }
 
@@ -1205,37 +1759,44 @@ pub fn craber_lose_health(
     }
 }
 
+/// Attackers take their gain out of the victim's energy, never more than the victim has. The
+/// victim's loss goes to the pool now and the pool pays the attacker in `apply_energy_deltas`.
 pub fn craber_attack_lose_health_add_energy(
     mut craber_attack_events: MessageReader<CraberAttackEvent>,
-    mut attacked_query: Query<&mut Health>,
-    mut attacker_query: Query<&mut Energy>,
+    mut attacked_query: Query<(&mut Health, &mut Energy)>,
+    mut attacker_query: Query<&mut EnergyDelta>,
+    mut energy_pool: ResMut<EnergyPool>,
 ) {
     for craber_attack_event in craber_attack_events.read() {
-        if let Ok(mut health) = attacked_query.get_mut(craber_attack_event.attacked_craber_entity) {
+        if let Ok((mut health, mut victim_energy)) =
+            attacked_query.get_mut(craber_attack_event.attacked_craber_entity)
+        {
             if health.health <= 0. {
                 continue;
             }
-            if health.health - craber_attack_event.attack_damage < 0. {
+            let Ok(mut attacker_delta) =
+                attacker_query.get_mut(craber_attack_event.attacking_craber_entity)
+            else {
+                continue;
+            };
+            let energy_to_gain = if health.health - craber_attack_event.attack_damage < 0. {
                 let actual_damage = health.health;
                 let energy_modifier = if craber_attack_event.attack_damage > 0.0 {
                     actual_damage / craber_attack_event.attack_damage
                 } else {
                     0.0
                 };
-                if let Ok(mut energy) =
-                    attacker_query.get_mut(craber_attack_event.attacking_craber_entity)
-                {
-                    energy.energy += craber_attack_event.energy_to_gain * energy_modifier;
-                }
                 health.health = 0.0;
-                continue;
-            }
-            if let Ok(mut energy) =
-                attacker_query.get_mut(craber_attack_event.attacking_craber_entity)
-            {
+                craber_attack_event.energy_to_gain * energy_modifier
+            } else {
                 health.health -= craber_attack_event.attack_damage;
-                energy.energy += craber_attack_event.energy_to_gain;
-            }
+                craber_attack_event.energy_to_gain
+            };
+            let taken = energy_to_gain.min(victim_energy.energy.max(0.0));
+            let before = victim_energy.energy;
+            victim_energy.energy -= taken;
+            energy_pool.deposit(before as f64 - victim_energy.energy as f64);
+            attacker_delta.0 += taken;
         }
     }
 }
@@ -1322,13 +1883,13 @@ fn debug_check_finite(
 fn draw_vision_debug(
     debug: Res<DebugVisionEnabled>,
     mut gizmos: Gizmos,
-    craber_query: Query<(&Transform, &Children, &LinearVelocity), With<Craber>>,
+    craber_query: Query<(&Transform, &Children, &LinearVelocity, &Brain), With<Craber>>,
     vision_query: Query<&Vision>,
 ) {
     if !debug.0 {
         return;
     }
-    for (transform, children, linear_vel) in craber_query.iter() {
+    for (transform, children, linear_vel, brain) in craber_query.iter() {
         let pos = transform.translation.truncate();
         let facing = (transform.rotation * Vec3::Y).truncate().normalize();
 
@@ -1367,6 +1928,23 @@ fn draw_vision_debug(
                     );
                     gizmos.line_2d(pos, pos + wall_dir * 40.0, Color::srgb(1.0, 1.0, 0.0));
                 }
+                // Eye rays up to their first hit, coloured like the nearest-object lines; grey
+                // when they see nothing
+                for eye in &brain.eyes {
+                    let eye_dir = Vec2::from_angle(-eye.angle).rotate(-facing);
+                    let (length, color) = match vision.eye_hits[eye.slot as usize] {
+                        Some(hit) => (
+                            hit.distance,
+                            match hit.kind {
+                                EyeHitKind::Food => Color::srgb(0.0, 1.0, 0.0),
+                                EyeHitKind::Craber => Color::srgb(1.0, 0.0, 0.0),
+                                EyeHitKind::Wall => Color::srgb(1.0, 1.0, 0.0),
+                            },
+                        ),
+                        None => (eye.range, Color::srgb(0.4, 0.4, 0.4)),
+                    };
+                    gizmos.line_2d(pos, pos + eye_dir * length, color);
+                }
             }
         }
     }
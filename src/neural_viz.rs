@@ -66,6 +66,16 @@ fn neuron_label(neuron_type: NeuronType) -> &'static str {
         NeuronType::NearestCraberGeneticCloseness => "GEN.C",
         NeuronType::BrainInterval => "INT",
         NeuronType::LastReproduced => "REPR.T",
+        NeuronType::CraberAngularVelocity => "SPIN",
+        NeuronType::VelocityDirection => "V.DIR",
+        NeuronType::RecentlyCollided => "BUMP",
+        NeuronType::WasAttacked => "BITTEN",
+        NeuronType::PheromoneConcentration(channel) => ["P0.C", "P1.C"][channel as usize],
+        NeuronType::PheromoneGradient(channel) => ["P0.G", "P1.G"][channel as usize],
+        NeuronType::EyeDistance(slot) => ["E0.DST", "E1.DST", "E2.DST", "E3.DST"][slot as usize],
+        NeuronType::EyeFood(slot) => ["E0.F", "E1.F", "E2.F", "E3.F"][slot as usize],
+        NeuronType::EyeCraber(slot) => ["E0.C", "E1.C", "E2.C", "E3.C"][slot as usize],
+        NeuronType::EyeWall(slot) => ["E0.W", "E1.W", "E2.W", "E3.W"][slot as usize],
         NeuronType::Hidden => "H",
         NeuronType::KickStrength => "K.STR",
         NeuronType::KickRate => "K.RT",
@@ -77,6 +87,8 @@ fn neuron_label(neuron_type: NeuronType) -> &'static str {
         NeuronType::WantSexualReproduction => "SEX",
         NeuronType::WantToAttack => "ATK",
         NeuronType::WantToDefend => "DEF",
+        NeuronType::WantGeneTransfer => "HGT",
+        NeuronType::EmitPheromone(channel) => ["P0.EMIT", "P1.EMIT"][channel as usize],
     }
 }
 
@@ -0,0 +1,216 @@
+use bevy::prelude::*;
+use bevy_egui::egui;
+use std::ops::RangeInclusive;
+
+use crate::brain::BrainEvaluation;
+use crate::config::SimConfig;
+
+pub const PRESET_DIR: &str = "presets";
+
+#[derive(Message)]
+pub struct SavePresetEvent {
+    pub name: String,
+}
+
+#[derive(Message)]
+pub struct LoadPresetEvent {
+    pub path: String,
+}
+
+/// Lists `.ron` presets in `PRESET_DIR`, sorted by path.
+pub fn list_preset_files() -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(PRESET_DIR) else {
+        return Vec::new();
+    };
+    let mut files: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    files
+}
+
+pub fn save_preset(mut save_events: MessageReader<SavePresetEvent>, config: Res<SimConfig>) {
+    for event in save_events.read() {
+        let path = format!("{}/{}.ron", PRESET_DIR, event.name);
+        let result = config.to_ron_string().and_then(|text| {
+            std::fs::create_dir_all(PRESET_DIR)
+                .and_then(|_| std::fs::write(&path, text))
+                .map_err(|e| format!("failed to write {path}: {e}"))
+        });
+        match result {
+            Ok(()) => info!("Saved parameter preset to {}", path),
+            Err(e) => warn!("Could not save parameter preset: {}", e),
+        }
+    }
+}
+
+pub fn load_preset(mut load_events: MessageReader<LoadPresetEvent>, mut config: ResMut<SimConfig>) {
+    for event in load_events.read() {
+        match SimConfig::read_from_file(&event.path) {
+            Ok(preset) => {
+                config.apply_live(&preset);
+                info!("Loaded parameter preset from {}", event.path);
+            }
+            Err(e) => warn!("Could not load parameter preset: {}", e),
+        }
+    }
+}
+
+fn param<T: egui::emath::Numeric>(
+    ui: &mut egui::Ui,
+    label: &str,
+    value: &mut T,
+    range: RangeInclusive<T>,
+    speed: f64,
+) {
+    ui.label(label);
+    ui.add(egui::DragValue::new(value).range(range).speed(speed));
+    ui.end_row();
+}
+
+fn param_group(ui: &mut egui::Ui, title: &str, add_rows: impl FnOnce(&mut egui::Ui)) {
+    egui::CollapsingHeader::new(title)
+        .default_open(false)
+        .show(ui, |ui| {
+            egui::Grid::new(title)
+                .num_columns(2)
+                .striped(true)
+                .show(ui, add_rows);
+        });
+}
+
+/// Contents of the Parameters window: live `SimConfig` editing and presets.
+pub fn draw_parameters(
+    ui: &mut egui::Ui,
+    config: &mut SimConfig,
+    preset_name: &mut String,
+    preset_path: &mut String,
+    save_preset: &mut MessageWriter<SavePresetEvent>,
+    load_preset: &mut MessageWriter<LoadPresetEvent>,
+) {
+    param_group(ui, "Mutation", |ui| {
+        param(ui, "Mutation chance", &mut config.mutation_chance, 0.0..=1.0, 0.001);
+        param(ui, "Mutation amount", &mut config.mutation_amount, 0.0..=10.0, 0.01);
+        param(ui, "Insertion chance", &mut config.insertion_chance, 0.0..=1.0, 0.001);
+        param(ui, "Deletion chance", &mut config.deletion_chance, 0.0..=1.0, 0.001);
+    });
+    param_group(ui, "Body genes", |ui| {
+        param(ui, "Mutation chance", &mut config.gene_mutation_chance, 0.0..=1.0, 0.001);
+        param(ui, "Mutation amount", &mut config.gene_mutation_amount, 0.0..=1.0, 0.001);
+        param(ui, "Upkeep cost", &mut config.body_upkeep_cost, 0.0..=10.0, 0.001);
+    });
+    param_group(ui, "Gene transfer", |ui| {
+        param(ui, "Threshold", &mut config.gene_transfer_threshold, 0.0..=10.0, 0.01);
+        param(ui, "Max connections", &mut config.gene_transfer_max_connections, 1..=50, 0.1);
+        param(ui, "Energy cost", &mut config.gene_transfer_cost, 0.0..=100.0, 0.1);
+        param(ui, "Cooldown (s)", &mut config.gene_transfer_cooldown, 0.0..=60.0, 0.05);
+    });
+    param_group(ui, "Speciation", |ui| {
+        param(ui, "Compatibility threshold", &mut config.species_compatibility_threshold, 0.0..=1.0, 0.005);
+        param(ui, "Update interval (s)", &mut config.speciation_interval, 0.1..=60.0, 0.05);
+    });
+    param_group(ui, "Food", |ui| {
+        param(ui, "Spawn interval (s)", &mut config.food_spawn_rate, 0.0001..=10.0, 0.0001);
+        param(ui, "Max food", &mut config.max_food_count, 0..=100_000, 10.0);
+        param(ui, "Min energy", &mut config.food_energy_min, 0.0..=999.0, 0.1);
+        // Food energy is drawn from min..max, which must not be empty
+        let min_energy = config.food_energy_min;
+        param(ui, "Max energy", &mut config.food_energy_max, min_energy + 0.1..=1000.0, 0.1);
+    });
+    param_group(ui, "Population", |ui| {
+        param(ui, "Spawn interval (s)", &mut config.craber_spawn_rate, 0.001..=60.0, 0.01);
+        param(ui, "Max crabers", &mut config.max_crabers, 0..=100_000, 10.0);
+        param(ui, "Random spawns up to", &mut config.max_crabers_spawner, 0..=100_000, 1.0);
+        param(ui, "Crabers per spawn", &mut config.craber_spawn_multiplier, 1..=100, 0.1);
+    });
+    param_group(ui, "Energy", |ui| {
+        param(ui, "Consumption rate", &mut config.energy_consumption_rate, 0.0..=10.0, 0.001);
+        param(ui, "Brain tick cost", &mut config.brain_tick_energy_cost, 0.0..=10.0, 0.001);
+        param(ui, "Kick cost modifier", &mut config.kick_energy_modifier, 0.0..=100.0, 0.01);
+        param(ui, "Healing rate", &mut config.healing_rate, 0.0..=10.0, 0.001);
+        param(ui, "Healing cost", &mut config.healing_cost, 0.0..=100.0, 0.01);
+        param(ui, "Death food share", &mut config.death_energy_factor, 0.0..=1.0, 0.01);
+    });
+    param_group(ui, "Reproduction", |ui| {
+        param(ui, "Required energy", &mut config.required_reproduce_energy, 0.0..=1000.0, 0.5);
+        param(ui, "Energy given to child", &mut config.reproduce_energy, 0.0..=1000.0, 0.5);
+    });
+    param_group(ui, "Movement", |ui| {
+        param(ui, "Max impulse", &mut config.max_impulse, 0.0..=10_000.0, 1.0);
+        param(ui, "Kick threshold", &mut config.kick_threshold, 0.0..=1.0, 0.001);
+        param(ui, "Kick steepness", &mut config.kick_steepness, 0.0..=10.0, 0.01);
+        param(ui, "Kick rate steepness", &mut config.kick_rate_steepness, 0.0..=10.0, 0.01);
+        param(ui, "Max angular impulse", &mut config.max_angular_impulse, 0.0..=10.0, 0.001);
+        param(ui, "Rotation threshold", &mut config.rotation_threshold, 0.0..=1.0, 0.001);
+        param(ui, "Rotation rate steepness", &mut config.rotation_rate_steepness, 0.0..=10.0, 0.01);
+    });
+    param_group(ui, "Brain", |ui| {
+        ui.label("Evaluation");
+        egui::ComboBox::from_id_salt("brain_evaluation")
+            .selected_text(format!("{:?}", config.brain_evaluation))
+            .show_ui(ui, |ui| {
+                for evaluation in [BrainEvaluation::Relaxation, BrainEvaluation::Topological] {
+                    ui.selectable_value(&mut config.brain_evaluation, evaluation, format!("{evaluation:?}"));
+                }
+            });
+        ui.end_row();
+        param(ui, "Relaxation steps", &mut config.brain_relaxation_steps, 1..=16, 0.05);
+    });
+    param_group(ui, "Pheromones", |ui| {
+        param(ui, "Diffusion rate", &mut config.pheromone_diffusion_rate, 0.0..=15.0, 0.01);
+        param(ui, "Evaporation rate", &mut config.pheromone_evaporation_rate, 0.0..=10.0, 0.001);
+        param(ui, "Emission rate", &mut config.pheromone_emission_rate, 0.0..=100.0, 0.05);
+        param(ui, "Energy cost", &mut config.pheromone_energy_cost, 0.0..=10.0, 0.001);
+    });
+    param_group(ui, "Drag", |ui| {
+        param(ui, "Linear drag", &mut config.linear_drag_coefficient, 0.0..=10.0, 0.001);
+        param(ui, "Angular drag", &mut config.angular_drag_coefficient, 0.0..=100.0, 0.01);
+    });
+
+    ui.separator();
+    ui.horizontal(|ui| {
+        ui.add(egui::TextEdit::singleline(preset_name).desired_width(120.0).hint_text("preset name"));
+        // Names end up in a file path, so keep them to a single path component
+        let valid_name = !preset_name.is_empty()
+            && preset_name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if ui
+            .add_enabled(valid_name, egui::Button::new("Save preset"))
+            .clicked()
+        {
+            save_preset.write(SavePresetEvent {
+                name: preset_name.clone(),
+            });
+        }
+    });
+    ui.horizontal(|ui| {
+        // Files are only listed while the dropdown is open
+        egui::ComboBox::from_id_salt("preset_path")
+            .selected_text(if preset_path.is_empty() {
+                "Choose preset..."
+            } else {
+                preset_path.as_str()
+            })
+            .show_ui(ui, |ui| {
+                for path in list_preset_files() {
+                    let label = path.clone();
+                    ui.selectable_value(&mut *preset_path, path, label);
+                }
+            });
+        if ui
+            .add_enabled(!preset_path.is_empty(), egui::Button::new("Load preset"))
+            .clicked()
+        {
+            load_preset.write(LoadPresetEvent {
+                path: preset_path.clone(),
+            });
+        }
+    });
+    if ui.button("Reset to defaults").clicked() {
+        config.apply_live(&SimConfig::default());
+    }
+}
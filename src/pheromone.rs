@@ -0,0 +1,231 @@
+use bevy::asset::RenderAssetUsages;
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+use crate::brain::*;
+use crate::config::SimConfig;
+use crate::craber::*;
+
+/// Scalar chemical fields over the arena, one grid per channel. Crabers deposit into them through
+/// their `EmitPheromone` outputs; every fixed step the fields diffuse into neighbouring cells and
+/// evaporate.
+#[derive(Resource, Clone, Debug)]
+pub struct PheromoneField {
+    cell_size: f32,
+    cells_per_side: usize,
+    /// World position of the lower left corner of cell (0, 0).
+    min: Vec2,
+    /// Row-major concentrations, row 0 at the bottom.
+    channels: Vec<Vec<f32>>,
+    scratch: Vec<f32>,
+}
+
+impl PheromoneField {
+    /// Covers the arena inside the walls, `-world_size..world_size` on both axes.
+    pub fn new(config: &SimConfig) -> Self {
+        let cell_size = config.pheromone_cell_size.max(1.0);
+        let cells_per_side = ((config.world_size * 2.0 / cell_size).ceil() as usize).max(1);
+        let cell_count = cells_per_side * cells_per_side;
+        Self {
+            cell_size,
+            cells_per_side,
+            min: Vec2::splat(-config.world_size),
+            channels: vec![vec![0.0; cell_count]; PHEROMONE_CHANNELS as usize],
+            scratch: vec![0.0; cell_count],
+        }
+    }
+
+    pub fn cells_per_side(&self) -> usize {
+        self.cells_per_side
+    }
+
+    /// Concentrations of every channel, row-major.
+    pub fn channels(&self) -> &[Vec<f32>] {
+        &self.channels
+    }
+
+    /// Replaces the concentrations, which must match this field's channels and grid.
+    pub fn restore(&mut self, channels: Vec<Vec<f32>>) -> Result<(), String> {
+        let cell_count = self.cells_per_side * self.cells_per_side;
+        if channels.len() != self.channels.len() || channels.iter().any(|c| c.len() != cell_count) {
+            return Err(format!(
+                "pheromone field doesn't match the {} channels of {} cells",
+                self.channels.len(),
+                cell_count
+            ));
+        }
+        self.channels = channels;
+        Ok(())
+    }
+
+    /// Cell containing `position`, clamped to the grid.
+    fn cell(&self, position: Vec2) -> (usize, usize) {
+        let last = self.cells_per_side as f32 - 1.0;
+        let cell = ((position - self.min) / self.cell_size).floor();
+        (cell.x.clamp(0.0, last) as usize, cell.y.clamp(0.0, last) as usize)
+    }
+
+    fn value(&self, channel: usize, x: usize, y: usize) -> f32 {
+        self.channels[channel][y * self.cells_per_side + x]
+    }
+
+    pub fn deposit(&mut self, channel: usize, position: Vec2, amount: f32) {
+        let (x, y) = self.cell(position);
+        self.channels[channel][y * self.cells_per_side + x] += amount;
+    }
+
+    pub fn concentration(&self, channel: usize, position: Vec2) -> f32 {
+        let (x, y) = self.cell(position);
+        self.value(channel, x, y)
+    }
+
+    /// Central difference of the concentration around `position`, pointing uphill.
+    pub fn gradient(&self, channel: usize, position: Vec2) -> Vec2 {
+        let (x, y) = self.cell(position);
+        let last = self.cells_per_side - 1;
+        let dx = self.value(channel, (x + 1).min(last), y) - self.value(channel, x.saturating_sub(1), y);
+        let dy = self.value(channel, x, (y + 1).min(last)) - self.value(channel, x, y.saturating_sub(1));
+        Vec2::new(dx, dy)
+    }
+
+    /// Moves `diffusion` of the difference to the four neighbours into every cell, then keeps
+    /// `retention` of what is left. Edges reflect, so diffusion alone loses nothing.
+    fn step(&mut self, diffusion: f32, retention: f32) {
+        let side = self.cells_per_side;
+        for channel in self.channels.iter_mut() {
+            for y in 0..side {
+                for x in 0..side {
+                    let i = y * side + x;
+                    let center = channel[i];
+                    let left = if x > 0 { channel[i - 1] } else { center };
+                    let right = if x + 1 < side { channel[i + 1] } else { center };
+                    let down = if y > 0 { channel[i - side] } else { center };
+                    let up = if y + 1 < side { channel[i + side] } else { center };
+                    self.scratch[i] =
+                        (center + diffusion * (left + right + down + up - 4.0 * center)) * retention;
+                }
+            }
+            std::mem::swap(channel, &mut self.scratch);
+        }
+    }
+}
+
+pub fn diffuse_pheromones(mut field: ResMut<PheromoneField>, time: Res<Time>, config: Res<SimConfig>) {
+    let dt = time.delta_secs();
+    // Explicit diffusion is only stable while a cell gives away at most a quarter per neighbour
+    let diffusion = (config.pheromone_diffusion_rate * dt).clamp(0.0, 0.25);
+    let retention = (-config.pheromone_evaporation_rate * dt).exp();
+    field.step(diffusion, retention);
+}
+
+/// Deposits pheromone under every craber whose `EmitPheromone` outputs are positive, paying for it
+/// in energy.
+pub fn emit_pheromones(
+    mut field: ResMut<PheromoneField>,
+    mut query: Query<(&Transform, &Brain, &mut EnergyDelta)>,
+    time: Res<Time>,
+    config: Res<SimConfig>,
+) {
+    let dt = time.delta_secs();
+    for (transform, brain, mut energy_delta) in query.iter_mut() {
+        let position = transform.translation.truncate();
+        for channel in 0..PHEROMONE_CHANNELS {
+            let strength = brain
+                .output_value(NeuronType::EmitPheromone(channel))
+                .clamp(0.0, 1.0);
+            if strength <= 0.0 {
+                continue;
+            }
+            let amount = strength * config.pheromone_emission_rate * dt;
+            field.deposit(channel as usize, position, amount);
+            energy_delta.0 -= amount * config.pheromone_energy_cost;
+        }
+    }
+}
+
+/// Sprite showing the pheromone field, hidden until toggled with O.
+#[derive(Component)]
+pub struct PheromoneOverlay;
+
+/// Overlay colour of each channel.
+const CHANNEL_COLORS: [[f32; 3]; PHEROMONE_CHANNELS as usize] = [[1.0, 0.4, 0.9], [0.3, 0.9, 1.0]];
+
+pub fn setup_pheromone_overlay(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    field: Res<PheromoneField>,
+    config: Res<SimConfig>,
+) {
+    let side = field.cells_per_side() as u32;
+    let image = Image::new_fill(
+        Extent3d {
+            width: side,
+            height: side,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    commands.spawn((
+        Name::new("PheromoneOverlay"),
+        PheromoneOverlay,
+        Sprite {
+            image: images.add(image),
+            custom_size: Some(Vec2::splat(config.world_size * 2.0)),
+            ..default()
+        },
+        // Below crabers and food
+        Transform::from_xyz(0.0, 0.0, -1.0),
+        Visibility::Hidden,
+    ));
+}
+
+pub fn toggle_pheromone_overlay(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut overlay_query: Query<&mut Visibility, With<PheromoneOverlay>>,
+) {
+    if keyboard.just_pressed(KeyCode::KeyO) {
+        for mut visibility in overlay_query.iter_mut() {
+            visibility.toggle_visible_hidden();
+        }
+    }
+}
+
+pub fn draw_pheromone_overlay(
+    overlay_query: Query<(&Sprite, &Visibility), With<PheromoneOverlay>>,
+    mut images: ResMut<Assets<Image>>,
+    field: Res<PheromoneField>,
+) {
+    let Ok((sprite, visibility)) = overlay_query.single() else {
+        return;
+    };
+    if *visibility == Visibility::Hidden {
+        return;
+    }
+    let Some(data) = images.get_mut(&sprite.image).and_then(|image| image.data.as_mut()) else {
+        return;
+    };
+    let side = field.cells_per_side();
+    for (row, pixels) in data.chunks_exact_mut(side * 4).enumerate() {
+        // Image rows run top to bottom, field rows bottom to top
+        let y = side - 1 - row;
+        for (x, pixel) in pixels.chunks_exact_mut(4).enumerate() {
+            let mut color = [0.0f32; 3];
+            let mut alpha = 0.0f32;
+            for (channel, channel_color) in CHANNEL_COLORS.iter().enumerate() {
+                let value = field.value(channel, x, y);
+                let intensity = value / (1.0 + value);
+                for (c, channel_c) in color.iter_mut().zip(channel_color) {
+                    *c += channel_c * intensity;
+                }
+                alpha = alpha.max(intensity);
+            }
+            for (out, c) in pixel.iter_mut().zip(color) {
+                *out = (c.min(1.0) * 255.0) as u8;
+            }
+            pixel[3] = (alpha * 200.0) as u8;
+        }
+    }
+}
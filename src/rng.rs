@@ -0,0 +1,64 @@
+use bevy::prelude::*;
+use rand::SeedableRng;
+use rand::rngs::Xoshiro256PlusPlus;
+use serde::{Deserialize, Serialize};
+
+/// Generator behind every simulation stream. Portable, so a seed replays the same on any machine.
+pub type StreamRng = Xoshiro256PlusPlus;
+
+/// Consumers of `SimRng`. Each one draws from its own stream, so extra draws in one system
+/// don't shift the sequence seen by another.
+#[derive(Clone, Copy, Debug)]
+pub enum RngStream {
+    Food,
+    CraberSpawner,
+    /// Seeds the per-craber `CraberRng` streams.
+    Crabers,
+}
+
+/// All simulation randomness, derived from a single seed.
+#[derive(Resource, Clone, Serialize, Deserialize)]
+pub struct SimRng {
+    seed: u64,
+    food: StreamRng,
+    craber_spawner: StreamRng,
+    crabers: StreamRng,
+}
+
+impl SimRng {
+    pub fn new(seed: u64) -> Self {
+        let mut master = StreamRng::seed_from_u64(seed);
+        Self {
+            seed,
+            food: StreamRng::from_rng(&mut master),
+            craber_spawner: StreamRng::from_rng(&mut master),
+            crabers: StreamRng::from_rng(&mut master),
+        }
+    }
+
+    /// Seed taken from the OS, for runs that didn't ask for one.
+    pub fn from_entropy() -> Self {
+        Self::new(rand::random())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn stream(&mut self, stream: RngStream) -> &mut StreamRng {
+        match stream {
+            RngStream::Food => &mut self.food,
+            RngStream::CraberSpawner => &mut self.craber_spawner,
+            RngStream::Crabers => &mut self.crabers,
+        }
+    }
+
+    /// Derives a new independent stream, e.g. for a freshly spawned entity.
+    pub fn fork(&mut self, stream: RngStream) -> StreamRng {
+        StreamRng::from_rng(self.stream(stream))
+    }
+}
+
+/// Per-craber stream used for its own decisions and its offspring's mutations.
+#[derive(Component)]
+pub struct CraberRng(pub StreamRng);
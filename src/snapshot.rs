@@ -0,0 +1,422 @@
+use avian2d::prelude::*;
+use bevy::ecs::query::QueryData;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::brain::*;
+use crate::common::*;
+use crate::config::SimConfig;
+use crate::craber::*;
+use crate::food::*;
+use crate::genetics::Genotype;
+use crate::genome::Genome;
+use crate::lineage::*;
+use crate::pheromone::PheromoneField;
+use crate::rng::{CraberRng, SimRng, StreamRng};
+use crate::spatial::SpatialIndex;
+use crate::species::SpeciesTracker;
+
+/// Bumped whenever the snapshot layout changes; older files are rejected on load.
+pub const SNAPSHOT_VERSION: u32 = 13;
+pub const SNAPSHOT_PATH: &str = "crabers_snapshot.ron";
+
+#[derive(Message)]
+pub struct SaveSnapshotEvent;
+
+#[derive(Message)]
+pub struct LoadSnapshotEvent;
+
+#[derive(Serialize, Deserialize)]
+pub struct CraberSnapshot {
+    pub lineage: u64,
+    pub brain: Brain,
+    pub genome: Genome,
+    pub genotype: Genotype,
+    pub health: Health,
+    pub energy: Energy,
+    pub generation: u32,
+    pub age: f32,
+    pub children_count: u32,
+    pub last_reproduced: f32,
+    pub recently_collided: f32,
+    pub was_attacked: f32,
+    pub kick_accumulator: f32,
+    pub rotation_accumulator: f32,
+    pub brain_tick_accumulator: f32,
+    pub reproduce_cooldown: Timer,
+    pub gene_transfer_cooldown: Timer,
+    pub rng: StreamRng,
+    pub transform: Transform,
+    pub linear_velocity: Vec2,
+    pub angular_velocity: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FoodSnapshot {
+    pub transform: Transform,
+    pub energy_value: f32,
+}
+
+/// Everything needed to resume a simulation run in a fresh app.
+#[derive(Serialize, Deserialize)]
+pub struct WorldSnapshot {
+    pub version: u32,
+    /// Virtual time at save, used to rebase the stats history on load.
+    pub elapsed_secs: f64,
+    pub crabers: Vec<CraberSnapshot>,
+    pub food: Vec<FoodSnapshot>,
+    pub food_spawn_timer: Timer,
+    pub craber_spawn_timer: Timer,
+    pub stats: SimulationStats,
+    pub innovations: InnovationRegistry,
+    pub species: SpeciesTracker,
+    pub lineage: LineageTree,
+    pub energy_pool: EnergyPool,
+    /// Parameters at save. The world layout ones only apply at startup and are kept on load.
+    pub config: SimConfig,
+    pub rng: SimRng,
+    /// Concentrations of each pheromone channel, row-major.
+    pub pheromones: Vec<Vec<f32>>,
+}
+
+impl WorldSnapshot {
+    pub fn write_to_file(&self, path: &str) -> Result<(), String> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| format!("failed to serialize snapshot: {e}"))?;
+        std::fs::write(path, text).map_err(|e| format!("failed to write {path}: {e}"))
+    }
+
+    pub fn read_from_file(path: &str) -> Result<Self, String> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| format!("failed to read {path}: {e}"))?;
+        let snapshot: WorldSnapshot =
+            ron::from_str(&text).map_err(|e| format!("failed to parse {path}: {e}"))?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(format!(
+                "unsupported snapshot version {} (expected {})",
+                snapshot.version, SNAPSHOT_VERSION
+            ));
+        }
+        snapshot.config.validate().map_err(|e| format!("{path}: {e}"))?;
+        for (i, craber) in snapshot.crabers.iter().enumerate() {
+            craber
+                .brain
+                .validate()
+                .map_err(|e| format!("craber {i} in {path} has an invalid brain: {e}"))?;
+            craber
+                .genome
+                .validate()
+                .map_err(|e| format!("craber {i} in {path} has invalid body genes: {e}"))?;
+            craber
+                .genotype
+                .validate()
+                .map_err(|e| format!("craber {i} in {path} has an invalid genotype: {e}"))?;
+        }
+        Ok(snapshot)
+    }
+}
+
+#[derive(QueryData)]
+pub struct CraberSnapshotQuery {
+    lineage: &'static Lineage,
+    brain: &'static Brain,
+    genome: &'static Genome,
+    genotype: &'static Genotype,
+    health: &'static Health,
+    energy: &'static Energy,
+    generation: &'static Generation,
+    age: &'static CraberAge,
+    children_count: &'static ChildrenCount,
+    last_reproduced: &'static LastReproducedValue,
+    recently_collided: &'static RecentlyCollidedValue,
+    was_attacked: &'static WasAttackedValue,
+    kick_accumulator: &'static KickAccumulator,
+    rotation_accumulator: &'static RotationAccumulator,
+    brain_tick_accumulator: &'static BrainTickAccumulator,
+    reproduce_cooldown: &'static ReproduceCooldown,
+    gene_transfer_cooldown: &'static GeneTransferCooldown,
+    rng: &'static CraberRng,
+    transform: &'static Transform,
+    linear_velocity: &'static LinearVelocity,
+    angular_velocity: &'static AngularVelocity,
+}
+
+pub fn snapshot_hotkeys(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut save_events: MessageWriter<SaveSnapshotEvent>,
+    mut load_events: MessageWriter<LoadSnapshotEvent>,
+) {
+    if keyboard.just_pressed(KeyCode::F5) {
+        save_events.write(SaveSnapshotEvent);
+    }
+    if keyboard.just_pressed(KeyCode::F9) {
+        load_events.write(LoadSnapshotEvent);
+    }
+}
+
+/// Simulation-wide resources that are saved to and restored from snapshots as a whole.
+#[derive(SystemParam)]
+pub struct SnapshotResources<'w> {
+    food_timer: ResMut<'w, FoodSpawnTimer>,
+    craber_timer: ResMut<'w, CraberSpawnTimer>,
+    stats: ResMut<'w, SimulationStats>,
+    innovations: ResMut<'w, InnovationRegistry>,
+    species: ResMut<'w, SpeciesTracker>,
+    lineage: ResMut<'w, LineageTree>,
+    pheromones: ResMut<'w, PheromoneField>,
+    energy_pool: ResMut<'w, EnergyPool>,
+}
+
+pub fn save_world_snapshot(
+    mut save_events: MessageReader<SaveSnapshotEvent>,
+    craber_query: Query<CraberSnapshotQuery, (With<Craber>, Without<Dying>)>,
+    food_query: Query<(&Food, &Transform)>,
+    resources: SnapshotResources,
+    sim_rng: Res<SimRng>,
+    config: Res<SimConfig>,
+    time: Res<Time>,
+) {
+    if save_events.read().count() == 0 {
+        return;
+    }
+    let crabers = craber_query
+        .iter()
+        .map(|c| CraberSnapshot {
+            lineage: c.lineage.0,
+            brain: c.brain.clone(),
+            genome: *c.genome,
+            genotype: c.genotype.clone(),
+            health: *c.health,
+            energy: *c.energy,
+            generation: c.generation.generation_id,
+            age: c.age.0,
+            children_count: c.children_count.0,
+            last_reproduced: c.last_reproduced.0,
+            recently_collided: c.recently_collided.0,
+            was_attacked: c.was_attacked.0,
+            kick_accumulator: c.kick_accumulator.0,
+            rotation_accumulator: c.rotation_accumulator.0,
+            brain_tick_accumulator: c.brain_tick_accumulator.0,
+            reproduce_cooldown: c.reproduce_cooldown.timer.clone(),
+            gene_transfer_cooldown: c.gene_transfer_cooldown.timer.clone(),
+            rng: c.rng.0.clone(),
+            transform: *c.transform,
+            linear_velocity: c.linear_velocity.0,
+            angular_velocity: c.angular_velocity.0,
+        })
+        .collect();
+    let food = food_query
+        .iter()
+        .map(|(food, transform)| FoodSnapshot {
+            transform: *transform,
+            energy_value: food.energy_value,
+        })
+        .collect();
+    let snapshot = WorldSnapshot {
+        version: SNAPSHOT_VERSION,
+        elapsed_secs: time.elapsed_secs_f64(),
+        crabers,
+        food,
+        food_spawn_timer: resources.food_timer.0.clone(),
+        craber_spawn_timer: resources.craber_timer.0.clone(),
+        stats: resources.stats.clone(),
+        innovations: resources.innovations.clone(),
+        species: resources.species.clone(),
+        lineage: resources.lineage.clone(),
+        energy_pool: resources.energy_pool.clone(),
+        config: config.clone(),
+        rng: sim_rng.clone(),
+        pheromones: resources.pheromones.channels().to_vec(),
+    };
+    match snapshot.write_to_file(SNAPSHOT_PATH) {
+        Ok(()) => info!(
+            "Saved world snapshot to {} ({} crabers, {} food)",
+            SNAPSHOT_PATH,
+            snapshot.crabers.len(),
+            snapshot.food.len()
+        ),
+        Err(e) => warn!("Could not save world snapshot: {}", e),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn load_world_snapshot(
+    mut commands: Commands,
+    mut load_events: MessageReader<LoadSnapshotEvent>,
+    mut visuals: CraberVisuals,
+    mut sim_rng: ResMut<SimRng>,
+    mut config: ResMut<SimConfig>,
+    mut spatial_index: ResMut<SpatialIndex>,
+    craber_entities: Query<Entity, With<Craber>>,
+    food_entities: Query<Entity, With<Food>>,
+    mut food_spawn_events: MessageWriter<FoodSpawnEvent>,
+    mut resources: SnapshotResources,
+    mut selected: ResMut<SelectedEntity>,
+    time: Res<Time>,
+) {
+    if load_events.read().count() == 0 {
+        return;
+    }
+    let mut snapshot = match WorldSnapshot::read_from_file(SNAPSHOT_PATH) {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            warn!("Could not load world snapshot: {}", e);
+            return;
+        }
+    };
+    // The grid follows the world layout, which is only read at startup
+    if let Err(e) = resources.pheromones.restore(std::mem::take(&mut snapshot.pheromones)) {
+        warn!("Could not load world snapshot: {}", e);
+        return;
+    }
+
+    for entity in craber_entities.iter().chain(food_entities.iter()) {
+        commands.entity(entity).despawn();
+    }
+    // Loads usually happen while paused, before a fixed step could see the food go
+    spatial_index.clear_food();
+    config.apply_live(&snapshot.config);
+    *selected = SelectedEntity::default();
+
+    let craber_count = snapshot.crabers.len();
+    let food_count = snapshot.food.len();
+    // Dying crabers aren't saved, so whatever the saved bodies don't hold belongs to the pool
+    let held: f64 = snapshot.crabers.iter().map(|c| c.energy.energy as f64).sum::<f64>()
+        + snapshot.food.iter().map(|f| f.energy_value as f64).sum::<f64>();
+    *resources.energy_pool = EnergyPool {
+        energy: snapshot.energy_pool.total - held,
+        total: snapshot.energy_pool.total,
+    };
+    let mut lineage = snapshot.lineage;
+    for craber in snapshot.crabers {
+        let spawn_event = SpawnEvent {
+            position: craber.transform.translation,
+            roation: craber.transform.rotation,
+            craber: Craber {},
+            generation: craber.generation,
+            parents: Vec::new(),
+            new_brain: craber.brain,
+            genome: craber.genome,
+            genotype: craber.genotype,
+            health: craber.health,
+            energy: craber.energy,
+        };
+        let entity = spawn_craber_entity(&mut commands, &mut visuals, &mut sim_rng, &config, &spawn_event);
+        commands.entity(entity).insert((
+            craber.transform,
+            LinearVelocity(craber.linear_velocity),
+            AngularVelocity(craber.angular_velocity),
+            CraberAge(craber.age),
+            ChildrenCount(craber.children_count),
+            LastReproducedValue(craber.last_reproduced),
+            RecentlyCollidedValue(craber.recently_collided),
+            WasAttackedValue(craber.was_attacked),
+            KickAccumulator(craber.kick_accumulator),
+            RotationAccumulator(craber.rotation_accumulator),
+            BrainTickAccumulator(craber.brain_tick_accumulator),
+            ReproduceCooldown {
+                timer: craber.reproduce_cooldown,
+            },
+            GeneTransferCooldown {
+                timer: craber.gene_transfer_cooldown,
+            },
+            CraberRng(craber.rng),
+            Lineage(craber.lineage),
+        ));
+        if let Some(record) = lineage.records.get_mut(&craber.lineage) {
+            record.entity = Some(entity);
+        }
+    }
+    // Spawning forked new craber streams, the saved ones continue where the run left off
+    *sim_rng = snapshot.rng;
+    for food in snapshot.food {
+        food_spawn_events.write(FoodSpawnEvent {
+            transform: food.transform,
+            food_energy: food.energy_value,
+        });
+    }
+
+    resources.food_timer.0 = snapshot.food_spawn_timer;
+    resources.craber_timer.0 = snapshot.craber_spawn_timer;
+    *resources.stats = snapshot.stats;
+    *resources.innovations = snapshot.innovations;
+    *resources.species = snapshot.species;
+    *resources.lineage = lineage;
+    // Charts and records are keyed by elapsed time, which restarts in a fresh app
+    let time_offset = time.elapsed_secs_f64() - snapshot.elapsed_secs;
+    resources.stats.shift_history_time(time_offset);
+    resources.species.shift_time(time_offset);
+    resources.lineage.shift_time(time_offset);
+
+    info!(
+        "Loaded world snapshot from {} ({} crabers, {} food)",
+        SNAPSHOT_PATH, craber_count, food_count
+    );
+}
+
+type History = VecDeque<[f64; 2]>;
+
+impl SimulationStats {
+    fn histories_mut(&mut self) -> [&mut History; 47] {
+        [
+            &mut self.craber_history,
+            &mut self.food_history,
+            &mut self.avg_age_history,
+            &mut self.max_age_history,
+            &mut self.avg_generation_history,
+            &mut self.max_generation_history,
+            &mut self.median_age_history,
+            &mut self.min_age_history,
+            &mut self.p25_age_history,
+            &mut self.p75_age_history,
+            &mut self.median_generation_history,
+            &mut self.min_generation_history,
+            &mut self.p25_generation_history,
+            &mut self.p75_generation_history,
+            &mut self.avg_energy_history,
+            &mut self.median_energy_history,
+            &mut self.p25_energy_history,
+            &mut self.p75_energy_history,
+            &mut self.avg_health_history,
+            &mut self.median_health_history,
+            &mut self.p25_health_history,
+            &mut self.p75_health_history,
+            &mut self.avg_children_history,
+            &mut self.max_children_history,
+            &mut self.median_children_history,
+            &mut self.p25_children_history,
+            &mut self.p75_children_history,
+            &mut self.avg_hidden_neurons_history,
+            &mut self.max_hidden_neurons_history,
+            &mut self.median_hidden_neurons_history,
+            &mut self.p25_hidden_neurons_history,
+            &mut self.p75_hidden_neurons_history,
+            &mut self.avg_connections_history,
+            &mut self.max_connections_history,
+            &mut self.median_connections_history,
+            &mut self.p25_connections_history,
+            &mut self.p75_connections_history,
+            &mut self.birth_rate_history,
+            &mut self.death_rate_history,
+            &mut self.sexual_birth_rate_history,
+            &mut self.asexual_birth_rate_history,
+            &mut self.pool_energy_history,
+            &mut self.food_energy_history,
+            &mut self.craber_energy_history,
+            &mut self.total_energy_history,
+            &mut self.gene_transfer_rate_history,
+            &mut self.transferred_connections_history,
+        ]
+    }
+
+    /// Offsets the time axis of every recorded history by `offset` seconds.
+    pub fn shift_history_time(&mut self, offset: f64) {
+        for history in self.histories_mut() {
+            for sample in history.iter_mut() {
+                sample[0] += offset;
+            }
+        }
+    }
+}
@@ -0,0 +1,394 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+use crate::brain::*;
+use crate::common::*;
+use crate::config::SimConfig;
+use crate::craber::*;
+use crate::food::*;
+use crate::genome::Genome;
+
+/// Side of a grid cell. Close to the vision radius, so a vision query touches about 3x3 cells.
+const CELL_SIZE: f32 = 128.0;
+
+#[derive(Clone, Copy, Debug)]
+pub struct SpatialEntry {
+    pub entity: Entity,
+    pub position: Vec2,
+    pub radius: f32,
+}
+
+/// Axis-aligned wall rectangle.
+#[derive(Clone, Copy, Debug)]
+pub struct WallRect {
+    pub center: Vec2,
+    pub half_size: Vec2,
+}
+
+impl WallRect {
+    pub fn closest_point(&self, point: Vec2) -> Vec2 {
+        point.clamp(self.center - self.half_size, self.center + self.half_size)
+    }
+
+    /// Distance along the ray to where it enters the rectangle, 0 if it starts inside.
+    fn ray_distance(&self, origin: Vec2, direction: Vec2) -> Option<f32> {
+        let (min, max) = (self.center - self.half_size, self.center + self.half_size);
+        let (mut near, mut far) = (0.0f32, f32::INFINITY);
+        for axis in 0..2 {
+            if direction[axis] == 0.0 {
+                if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let a = (min[axis] - origin[axis]) / direction[axis];
+            let b = (max[axis] - origin[axis]) / direction[axis];
+            near = near.max(a.min(b));
+            far = far.min(a.max(b));
+        }
+        (near <= far).then_some(near)
+    }
+}
+
+/// Distance along the ray to where it enters the entry's circle, 0 if it starts inside.
+fn circle_ray_distance(entry: &SpatialEntry, origin: Vec2, direction: Vec2) -> Option<f32> {
+    let offset = origin - entry.position;
+    let b = offset.dot(direction);
+    let c = offset.length_squared() - entry.radius * entry.radius;
+    if c <= 0.0 {
+        return Some(0.0);
+    }
+    let discriminant = b * b - c;
+    if b > 0.0 || discriminant < 0.0 {
+        return None;
+    }
+    Some(-b - discriminant.sqrt())
+}
+
+/// Uniform grid over crabers and food for vision queries. Crabers move, so they are re-indexed
+/// every fixed step; food never moves and is only added or removed as it spawns and despawns.
+/// Cells keep their allocations between rebuilds.
+#[derive(Resource, Default)]
+pub struct SpatialIndex {
+    crabers: HashMap<IVec2, Vec<SpatialEntry>>,
+    food: HashMap<IVec2, Vec<SpatialEntry>>,
+    food_cells: HashMap<Entity, IVec2>,
+    walls: Vec<WallRect>,
+    /// Largest entry radius, so queries also reach bodies centred in a neighbouring cell.
+    max_radius: f32,
+}
+
+fn cell_of(position: Vec2) -> IVec2 {
+    (position / CELL_SIZE).floor().as_ivec2()
+}
+
+fn insert(cells: &mut HashMap<IVec2, Vec<SpatialEntry>>, entry: SpatialEntry) {
+    cells.entry(cell_of(entry.position)).or_default().push(entry);
+}
+
+/// Entries in the cells overlapping the box from `min` to `max`.
+/// Cells are visited row by row and entries in insertion order, so results are deterministic.
+fn entries_in_box(
+    cells: &HashMap<IVec2, Vec<SpatialEntry>>,
+    min: Vec2,
+    max: Vec2,
+) -> impl Iterator<Item = &SpatialEntry> {
+    let (min, max) = (cell_of(min), cell_of(max));
+    (min.y..=max.y)
+        .flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+        .filter_map(|cell| cells.get(&cell))
+        .flatten()
+}
+
+/// Entries whose surface lies within `range` of `position`, with that surface distance.
+fn entries_within(
+    cells: &HashMap<IVec2, Vec<SpatialEntry>>,
+    position: Vec2,
+    range: f32,
+    max_radius: f32,
+) -> impl Iterator<Item = (&SpatialEntry, f32)> {
+    let reach = Vec2::splat(range + max_radius);
+    entries_in_box(cells, position - reach, position + reach)
+        .filter_map(move |entry| {
+            let distance = entry.position.distance(position) - entry.radius;
+            (distance <= range).then_some((entry, distance))
+        })
+}
+
+fn nearest<'a>(entries: impl Iterator<Item = (&'a SpatialEntry, f32)>) -> Option<(&'a SpatialEntry, f32)> {
+    entries.fold(None, |best, (entry, distance)| match best {
+        Some((_, best_distance)) if best_distance <= distance => best,
+        _ => Some((entry, distance)),
+    })
+}
+
+impl SpatialIndex {
+    /// Forgets crabers and walls. Food stays indexed.
+    pub fn clear_moving(&mut self) {
+        for cell in self.crabers.values_mut() {
+            cell.clear();
+        }
+        self.walls.clear();
+    }
+
+    /// Forgets all food.
+    pub fn clear_food(&mut self) {
+        for cell in self.food.values_mut() {
+            cell.clear();
+        }
+        self.food_cells.clear();
+    }
+
+    pub fn insert_craber(&mut self, entry: SpatialEntry) {
+        self.max_radius = self.max_radius.max(entry.radius);
+        insert(&mut self.crabers, entry);
+    }
+
+    pub fn insert_food(&mut self, entry: SpatialEntry) {
+        self.max_radius = self.max_radius.max(entry.radius);
+        self.food_cells.insert(entry.entity, cell_of(entry.position));
+        insert(&mut self.food, entry);
+    }
+
+    pub fn remove_food(&mut self, entity: Entity) {
+        let Some(cell) = self.food_cells.remove(&entity) else {
+            return;
+        };
+        if let Some(entries) = self.food.get_mut(&cell)
+            && let Some(i) = entries.iter().position(|entry| entry.entity == entity)
+        {
+            // Keeps the order of the rest, so queries stay deterministic
+            entries.remove(i);
+        }
+    }
+
+    pub fn insert_wall(&mut self, wall: WallRect) {
+        self.walls.push(wall);
+    }
+
+    /// Crabers other than `exclude` whose body is within `range` of `position`.
+    pub fn crabers_within(
+        &self,
+        position: Vec2,
+        range: f32,
+        exclude: Entity,
+    ) -> impl Iterator<Item = (&SpatialEntry, f32)> {
+        entries_within(&self.crabers, position, range, self.max_radius)
+            .filter(move |(entry, _)| entry.entity != exclude)
+    }
+
+    pub fn food_within(&self, position: Vec2, range: f32) -> impl Iterator<Item = (&SpatialEntry, f32)> {
+        entries_within(&self.food, position, range, self.max_radius)
+    }
+
+    /// Closest craber other than `exclude`, and the distance to its surface.
+    pub fn nearest_craber(&self, position: Vec2, range: f32, exclude: Entity) -> Option<(&SpatialEntry, f32)> {
+        nearest(self.crabers_within(position, range, exclude))
+    }
+
+    /// Closest food, and the distance to its surface.
+    pub fn nearest_food(&self, position: Vec2, range: f32) -> Option<(&SpatialEntry, f32)> {
+        nearest(self.food_within(position, range))
+    }
+
+    /// Closest point on any wall within `range`, and its distance.
+    pub fn nearest_wall(&self, position: Vec2, range: f32) -> Option<(Vec2, f32)> {
+        self.walls
+            .iter()
+            .map(|wall| {
+                let point = wall.closest_point(position);
+                (point, point.distance(position))
+            })
+            .filter(|(_, distance)| *distance <= range)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// First food, craber other than `exclude` or wall hit by a ray of length `range` along the
+    /// unit vector `direction`. On equal distances food wins over crabers and crabers over walls.
+    pub fn ray_cast(&self, origin: Vec2, direction: Vec2, range: f32, exclude: Entity) -> Option<EyeHit> {
+        let end = origin + direction * range;
+        let reach = Vec2::splat(self.max_radius);
+        let (min, max) = (origin.min(end) - reach, origin.max(end) + reach);
+        let mut first: Option<EyeHit> = None;
+        let mut consider = |kind: EyeHitKind, distance: Option<f32>| {
+            if let Some(distance) = distance
+                && distance <= range
+                && first.is_none_or(|hit| distance < hit.distance)
+            {
+                first = Some(EyeHit { kind, distance });
+            }
+        };
+        for entry in entries_in_box(&self.food, min, max) {
+            consider(EyeHitKind::Food, circle_ray_distance(entry, origin, direction));
+        }
+        for entry in entries_in_box(&self.crabers, min, max) {
+            if entry.entity != exclude {
+                consider(EyeHitKind::Craber, circle_ray_distance(entry, origin, direction));
+            }
+        }
+        for wall in &self.walls {
+            consider(EyeHitKind::Wall, wall.ray_distance(origin, direction));
+        }
+        first
+    }
+}
+
+pub fn rebuild_spatial_index(
+    mut index: ResMut<SpatialIndex>,
+    config: Res<SimConfig>,
+    craber_query: Query<(Entity, &Transform, &Genome), With<Craber>>,
+    food_query: Query<(Entity, &Transform), With<Food>>,
+    new_food_query: Query<(Entity, &Transform), Added<Food>>,
+    mut removed_food: RemovedComponents<Food>,
+    wall_query: Query<(&Transform, &Wall)>,
+) {
+    index.clear_moving();
+    for (entity, transform, genome) in craber_query.iter() {
+        index.insert_craber(SpatialEntry {
+            entity,
+            position: transform.translation.truncate(),
+            radius: genome.size / 2.0,
+        });
+    }
+    for entity in removed_food.read() {
+        index.remove_food(entity);
+    }
+    let food_entry = |(entity, transform): (Entity, &Transform)| SpatialEntry {
+        entity,
+        position: transform.translation.truncate(),
+        radius: config.food_size / 2.0,
+    };
+    for food in new_food_query.iter() {
+        index.insert_food(food_entry(food));
+    }
+    // Removals are only kept for two frames, and fixed steps skip frames at slow speeds, while
+    // paused or on fast displays. Food whose removal was missed is dropped by indexing it anew.
+    if index.food_cells.len() != food_query.iter().len() {
+        index.clear_food();
+        for food in food_query.iter() {
+            index.insert_food(food_entry(food));
+        }
+    }
+    for (transform, wall) in wall_query.iter() {
+        index.insert_wall(WallRect {
+            center: transform.translation.truncate(),
+            half_size: wall.half_size,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(index: u32, position: Vec2, radius: f32) -> SpatialEntry {
+        SpatialEntry {
+            entity: Entity::from_raw_u32(index).unwrap(),
+            position,
+            radius,
+        }
+    }
+
+    fn wall(center: Vec2, half_size: Vec2) -> WallRect {
+        WallRect { center, half_size }
+    }
+
+    #[test]
+    fn food_despawned_between_fixed_steps_leaves_the_index() {
+        let mut app = App::new();
+        app.init_resource::<SpatialIndex>()
+            .insert_resource(SimConfig::default())
+            .add_systems(FixedUpdate, rebuild_spatial_index);
+        let food: Vec<Entity> = (0..3)
+            .map(|i| {
+                let position = Vec3::new(i as f32 * 10.0, 0.0, 0.0);
+                app.world_mut()
+                    .spawn((Food { energy_value: 1.0 }, Transform::from_translation(position)))
+                    .id()
+            })
+            .collect();
+        app.world_mut().run_schedule(FixedUpdate);
+        let food_count = |app: &App| {
+            app.world().resource::<SpatialIndex>().food_within(Vec2::ZERO, 100.0).count()
+        };
+        assert_eq!(food_count(&app), 3);
+
+        // Frames without a fixed step drop the removal events before the index sees them
+        app.world_mut().despawn(food[0]);
+        for _ in 0..3 {
+            app.update();
+        }
+        app.world_mut().run_schedule(FixedUpdate);
+        assert_eq!(food_count(&app), 2);
+        let nearest = app.world().resource::<SpatialIndex>().nearest_food(Vec2::ZERO, 100.0);
+        assert_eq!(nearest.map(|(entry, _)| entry.entity), Some(food[1]));
+    }
+
+    #[test]
+    fn circle_rays_hit_miss_and_start_inside() {
+        let circle = entry(1, Vec2::new(10.0, 0.0), 2.0);
+        assert_eq!(circle_ray_distance(&circle, Vec2::ZERO, Vec2::X), Some(8.0));
+        // Passing beside it, and pointing away from it
+        assert_eq!(circle_ray_distance(&circle, Vec2::ZERO, Vec2::Y), None);
+        assert_eq!(circle_ray_distance(&circle, Vec2::ZERO, Vec2::NEG_X), None);
+        assert_eq!(circle_ray_distance(&circle, Vec2::new(9.0, 0.5), Vec2::NEG_X), Some(0.0));
+    }
+
+    #[test]
+    fn wall_rays_hit_miss_and_start_inside() {
+        let wall = wall(Vec2::new(10.0, 0.0), Vec2::new(1.0, 5.0));
+        // Axis-parallel rays: straight in, alongside past the wall's end, away from it
+        assert_eq!(wall.ray_distance(Vec2::ZERO, Vec2::X), Some(9.0));
+        assert_eq!(wall.ray_distance(Vec2::new(10.0, -20.0), Vec2::Y), Some(15.0));
+        assert_eq!(wall.ray_distance(Vec2::new(0.0, 6.0), Vec2::X), None);
+        assert_eq!(wall.ray_distance(Vec2::ZERO, Vec2::NEG_X), None);
+        assert_eq!(wall.ray_distance(Vec2::new(10.0, 0.0), Vec2::Y), Some(0.0));
+
+        // Enters the near face at (9, 0)
+        let diagonal = Vec2::new(1.0, 1.0).normalize();
+        let distance = wall.ray_distance(Vec2::new(0.0, -9.0), diagonal).unwrap();
+        assert!((distance - 9.0 * 2.0f32.sqrt()).abs() < 1e-4);
+        assert_eq!(wall.ray_distance(Vec2::new(0.0, -20.0), diagonal), None);
+    }
+
+    #[test]
+    fn ray_cast_reports_the_first_hit_within_range() {
+        let me = Entity::from_raw_u32(1).unwrap();
+        let mut index = SpatialIndex::default();
+        // The caster's own body surrounds the origin and is skipped
+        index.insert_craber(entry(1, Vec2::ZERO, 5.0));
+        index.insert_craber(entry(2, Vec2::new(30.0, 0.0), 5.0));
+        index.insert_food(entry(3, Vec2::new(50.0, 0.0), 2.0));
+        index.insert_wall(wall(Vec2::new(100.0, 0.0), Vec2::new(5.0, 100.0)));
+
+        let hit = |direction, range| index.ray_cast(Vec2::ZERO, direction, range, me);
+        assert_eq!(
+            hit(Vec2::X, 200.0),
+            Some(EyeHit {
+                kind: EyeHitKind::Craber,
+                distance: 25.0
+            })
+        );
+        assert_eq!(hit(Vec2::X, 20.0), None);
+        assert_eq!(hit(Vec2::NEG_Y, 200.0), None);
+    }
+
+    #[test]
+    fn ray_cast_ties_prefer_food_then_crabers_then_walls() {
+        let me = Entity::from_raw_u32(1).unwrap();
+        let mut index = SpatialIndex::default();
+        // Every body's surface is 20 along the ray
+        let face = wall(Vec2::new(25.0, 0.0), Vec2::new(5.0, 50.0));
+        index.insert_wall(face);
+        index.insert_craber(entry(2, Vec2::new(22.0, 0.0), 2.0));
+        index.insert_food(entry(3, Vec2::new(21.0, 0.0), 1.0));
+        let kind = |index: &SpatialIndex| index.ray_cast(Vec2::ZERO, Vec2::X, 100.0, me).map(|hit| hit.kind);
+
+        assert_eq!(kind(&index), Some(EyeHitKind::Food));
+        index.remove_food(Entity::from_raw_u32(3).unwrap());
+        assert_eq!(kind(&index), Some(EyeHitKind::Craber));
+        index.clear_moving();
+        index.insert_wall(face);
+        assert_eq!(kind(&index), Some(EyeHitKind::Wall));
+    }
+}
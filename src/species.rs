@@ -0,0 +1,171 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::brain::*;
+use crate::common::push_sample;
+use crate::config::SimConfig;
+use crate::craber::*;
+
+pub const SPECIES_HISTORY_CAPACITY: usize = 600;
+
+/// Species a craber was assigned to at the last speciation pass.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Species(pub u32);
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SpeciesRecord {
+    pub id: u32,
+    /// Members are compared against this brain. `None` once the species is extinct.
+    pub representative: Option<Brain>,
+    pub born_at: f64,
+    pub extinct_at: Option<f64>,
+    pub size: usize,
+    pub peak_size: usize,
+}
+
+/// Every species seen in this run, living and extinct, plus their size history for the charts.
+#[derive(Resource, Clone, Serialize, Deserialize)]
+pub struct SpeciesTracker {
+    /// Indexed by species id.
+    pub species: Vec<SpeciesRecord>,
+    pub species_count_history: VecDeque<[f64; 2]>,
+    /// Sizes of species that were alive within the charted window, keyed by id.
+    pub size_history: BTreeMap<u32, VecDeque<[f64; 2]>>,
+    pub update_timer: Timer,
+}
+
+impl Default for SpeciesTracker {
+    fn default() -> Self {
+        Self {
+            species: Vec::new(),
+            species_count_history: VecDeque::with_capacity(SPECIES_HISTORY_CAPACITY),
+            size_history: BTreeMap::new(),
+            update_timer: Timer::from_seconds(1.0, TimerMode::Repeating),
+        }
+    }
+}
+
+impl SpeciesTracker {
+    pub fn living(&self) -> impl Iterator<Item = &SpeciesRecord> {
+        self.species.iter().filter(|s| s.extinct_at.is_none())
+    }
+
+    pub fn extinct_count(&self) -> usize {
+        self.species.len() - self.living().count()
+    }
+
+    /// Offsets every recorded time by `offset` seconds.
+    pub fn shift_time(&mut self, offset: f64) {
+        for record in self.species.iter_mut() {
+            record.born_at += offset;
+            if let Some(extinct_at) = record.extinct_at.as_mut() {
+                *extinct_at += offset;
+            }
+        }
+        for sample in self
+            .size_history
+            .values_mut()
+            .flatten()
+            .chain(self.species_count_history.iter_mut())
+        {
+            sample[0] += offset;
+        }
+    }
+}
+
+type SpeciesMember<'a> = (Entity, &'a Brain, Option<&'a mut Species>, Option<&'a mut Sprite>);
+
+/// Distinct, stable colour for a species id.
+pub fn species_color(id: u32) -> Color {
+    // Golden angle steps keep neighbouring ids far apart on the hue wheel
+    Color::hsl((id as f32 * 137.508) % 360.0, 0.75, 0.65)
+}
+
+/// Clusters living crabers into species. A craber stays in its species while its brain is
+/// within `species_compatibility_threshold` of the representative, otherwise it joins the
+/// first compatible living species or founds a new one.
+pub fn update_species(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<SimConfig>,
+    mut tracker: ResMut<SpeciesTracker>,
+    mut craber_query: Query<SpeciesMember, (With<Craber>, Without<Dying>)>,
+) {
+    let interval = std::time::Duration::from_secs_f32(config.speciation_interval);
+    if tracker.update_timer.duration() != interval {
+        tracker.update_timer.set_duration(interval);
+    }
+    if !tracker.update_timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    let now = time.elapsed_secs_f64();
+    let threshold = config.species_compatibility_threshold;
+
+    let mut sizes: BTreeMap<u32, usize> = BTreeMap::new();
+    let mut next_representatives: BTreeMap<u32, Brain> = BTreeMap::new();
+    for (entity, brain, species, sprite) in craber_query.iter_mut() {
+        let current = species.as_deref().map(|s| s.0);
+        let compatible = |record: &SpeciesRecord| {
+            record
+                .representative
+                .as_ref()
+                .is_some_and(|rep| 1.0 - brain.genetic_closeness(rep) < threshold)
+        };
+        let found = current
+            .and_then(|id| tracker.species.get(id as usize))
+            .filter(|record| compatible(record))
+            .or_else(|| tracker.living().find(|record| compatible(record)))
+            .map(|record| record.id);
+        let id = found.unwrap_or_else(|| {
+            let id = tracker.species.len() as u32;
+            tracker.species.push(SpeciesRecord {
+                id,
+                representative: Some(brain.clone()),
+                born_at: now,
+                extinct_at: None,
+                size: 0,
+                peak_size: 0,
+            });
+            id
+        });
+
+        *sizes.entry(id).or_default() += 1;
+        // The first member found becomes the representative for the next pass
+        next_representatives.entry(id).or_insert_with(|| brain.clone());
+        if current != Some(id) {
+            match species {
+                Some(mut species) => species.0 = id,
+                None => {
+                    commands.entity(entity).insert(Species(id));
+                }
+            }
+            if let Some(mut sprite) = sprite {
+                sprite.color = species_color(id);
+            }
+        }
+    }
+
+    let tracker = tracker.as_mut();
+    for record in tracker.species.iter_mut().filter(|s| s.extinct_at.is_none()) {
+        record.size = sizes.get(&record.id).copied().unwrap_or(0);
+        record.peak_size = record.peak_size.max(record.size);
+        record.representative = next_representatives.remove(&record.id);
+        if record.size == 0 {
+            record.extinct_at = Some(now);
+        }
+    }
+
+    let living = tracker.living().count();
+    push_sample(&mut tracker.species_count_history, SPECIES_HISTORY_CAPACITY, now, living as f64);
+    for record in tracker.species.iter().filter(|s| s.extinct_at == Some(now) || s.extinct_at.is_none()) {
+        let history = tracker.size_history.entry(record.id).or_default();
+        push_sample(history, SPECIES_HISTORY_CAPACITY, now, record.size as f64);
+    }
+    // Forget the size history of species that died out before the charted window
+    let window_start = tracker.species_count_history.front().map_or(now, |s| s[0]);
+    let species = &tracker.species;
+    tracker.size_history.retain(|id, _| {
+        species[*id as usize].extinct_at.is_none_or(|t| t >= window_start)
+    });
+}
@@ -0,0 +1,142 @@
+use bevy::prelude::*;
+use bevy_egui::{EguiContexts, egui, input::EguiWantsInput};
+use std::time::Duration;
+
+/// Selectable simulation speeds, as multiples of real time.
+pub const SPEED_STEPS: [f64; 7] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+const DEFAULT_SPEED_INDEX: usize = 2;
+/// Bevy's default cap on the frame step of `Time<Virtual>`, at 1x.
+const DEFAULT_MAX_DELTA: Duration = Duration::from_millis(250);
+
+/// Requested pause/speed state, applied to `Time<Virtual>` before each fixed main loop.
+/// Physics runs inside the fixed loop too, so it pauses and speeds up with the rest of the world.
+#[derive(Resource)]
+pub struct TimeControls {
+    pub paused: bool,
+    /// Index into `SPEED_STEPS`.
+    pub speed_index: usize,
+    /// Fixed ticks to run while paused.
+    pub pending_steps: u32,
+}
+
+impl Default for TimeControls {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            speed_index: DEFAULT_SPEED_INDEX,
+            pending_steps: 0,
+        }
+    }
+}
+
+impl TimeControls {
+    pub fn speed(&self) -> f64 {
+        SPEED_STEPS[self.speed_index]
+    }
+
+    pub fn faster(&mut self) {
+        self.speed_index = (self.speed_index + 1).min(SPEED_STEPS.len() - 1);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed_index = self.speed_index.saturating_sub(1);
+    }
+
+    /// Pauses (if needed) and queues a single fixed tick.
+    pub fn step(&mut self) {
+        self.paused = true;
+        self.pending_steps += 1;
+    }
+}
+
+/// Space: pause/resume, `.`: single step, `-`/`=`: slower/faster.
+pub fn time_control_hotkeys(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    egui_input: Res<EguiWantsInput>,
+    mut controls: ResMut<TimeControls>,
+) {
+    if egui_input.wants_any_keyboard_input() {
+        return;
+    }
+    if keyboard.just_pressed(KeyCode::Space) {
+        controls.paused = !controls.paused;
+    }
+    if keyboard.just_pressed(KeyCode::Period) {
+        controls.step();
+    }
+    if keyboard.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        controls.slower();
+    }
+    if keyboard.any_just_pressed([KeyCode::Equal, KeyCode::NumpadAdd]) {
+        controls.faster();
+    }
+}
+
+/// Runs right before the fixed main loop, so every timer, brain tick and physics step
+/// sees the same fixed delta at any speed; only the number of ticks per frame changes.
+pub fn apply_time_controls(
+    mut controls: ResMut<TimeControls>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut fixed_time: ResMut<Time<Fixed>>,
+) {
+    virtual_time.set_relative_speed_f64(controls.speed());
+    // Long frames are cut to `max_delta`, so raise it with the speed to keep fast runs fast
+    virtual_time.set_max_delta(DEFAULT_MAX_DELTA.mul_f64(controls.speed().max(1.0)));
+    if controls.paused {
+        virtual_time.pause();
+        if controls.pending_steps > 0 {
+            let timestep = fixed_time.timestep();
+            fixed_time.accumulate_overstep(timestep * controls.pending_steps);
+            controls.pending_steps = 0;
+        }
+    } else {
+        virtual_time.unpause();
+        controls.pending_steps = 0;
+    }
+}
+
+pub fn egui_time_controls(
+    mut contexts: EguiContexts,
+    mut controls: ResMut<TimeControls>,
+    fixed_time: Res<Time<Fixed>>,
+    mut initialized: Local<bool>,
+) {
+    let Ok(ctx) = contexts.ctx_mut() else { return };
+
+    if !*initialized {
+        *initialized = true;
+        return;
+    }
+
+    let transparent_frame = egui::Frame::new()
+        .fill(egui::Color32::from_rgba_unmultiplied(20, 20, 25, 200))
+        .corner_radius(6.0)
+        .inner_margin(6.0);
+
+    egui::Window::new("Time")
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 10.0])
+        .title_bar(false)
+        .resizable(false)
+        .frame(transparent_frame)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let pause_label = if controls.paused { "Resume" } else { "Pause" };
+                if ui.button(pause_label).on_hover_text("Space").clicked() {
+                    controls.paused = !controls.paused;
+                }
+                if ui.button("Step").on_hover_text(".").clicked() {
+                    controls.step();
+                }
+                ui.separator();
+                if ui.button("-").on_hover_text("-").clicked() {
+                    controls.slower();
+                }
+                ui.label(format!("{}x", controls.speed()));
+                if ui.button("+").on_hover_text("=").clicked() {
+                    controls.faster();
+                }
+                ui.separator();
+                ui.label(format!("t = {:.1}s", fixed_time.elapsed_secs()));
+            });
+        });
+}